@@ -0,0 +1,122 @@
+use bevy::prelude::*;
+use bevy::sprite::collide_aabb::collide;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rust_invaders::collision::{CollisionEvent, CollisionLayer, CollisionPlugin, CollisionSystem};
+use rust_invaders::components::{Enemy, FromPlayer, Laser, SpriteSize};
+
+const LASER_SIZE: (f32, f32) = (9., 54.);
+const ENEMY_SIZE: (f32, f32) = (144., 75.);
+const SCALE: f32 = 0.5;
+const FIELD: (f32, f32) = (2000., 2000.);
+
+#[derive(Default)]
+struct Hits(usize);
+
+type LaserQuery<'w, 's> =
+    Query<'w, 's, (&'static Transform, &'static SpriteSize), (With<Laser>, With<FromPlayer>)>;
+type EnemyQuery<'w, 's> = Query<'w, 's, (&'static Transform, &'static SpriteSize), With<Enemy>>;
+
+fn hits(a: (&Transform, &SpriteSize), b: (&Transform, &SpriteSize)) -> bool {
+    collide(
+        a.0.translation,
+        a.1 .0 * a.0.scale.truncate(),
+        b.0.translation,
+        b.1 .0 * b.0.scale.truncate(),
+    )
+    .is_some()
+}
+
+fn naive_hit_system(mut count: ResMut<Hits>, lasers: LaserQuery, enemies: EnemyQuery) {
+    count.0 = 0;
+    for laser in lasers.iter() {
+        for enemy in enemies.iter() {
+            if hits(laser, enemy) {
+                count.0 += 1;
+            }
+        }
+    }
+}
+
+fn event_hit_system(mut count: ResMut<Hits>, mut events: EventReader<CollisionEvent>) {
+    count.0 = events.iter().count();
+}
+
+fn spawn_field(app: &mut App, count: usize) {
+    let mut rng = StdRng::seed_from_u64(count as u64);
+    let mut random_transform = || Transform {
+        translation: Vec3::new(
+            rng.gen_range(-FIELD.0 / 2.0..FIELD.0 / 2.),
+            rng.gen_range(-FIELD.1 / 2.0..FIELD.1 / 2.),
+            0.,
+        ),
+        scale: Vec3::new(SCALE, SCALE, 1.),
+        ..default()
+    };
+
+    let lasers: Vec<_> = (0..count)
+        .map(|_| {
+            (
+                random_transform(),
+                Laser,
+                FromPlayer,
+                CollisionLayer::PlayerLaser,
+                SpriteSize::from(LASER_SIZE),
+            )
+        })
+        .collect();
+    let enemies: Vec<_> = (0..count)
+        .map(|_| {
+            (
+                random_transform(),
+                Enemy,
+                CollisionLayer::Enemy,
+                SpriteSize::from(ENEMY_SIZE),
+            )
+        })
+        .collect();
+
+    app.world.spawn_batch(lasers);
+    app.world.spawn_batch(enemies);
+}
+
+fn naive_app(count: usize) -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .init_resource::<Hits>()
+        .add_system(naive_hit_system);
+    spawn_field(&mut app, count);
+    app
+}
+
+fn grid_app(count: usize) -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugin(CollisionPlugin)
+        .init_resource::<Hits>()
+        .add_system(event_hit_system.after(CollisionSystem::Detect));
+    spawn_field(&mut app, count);
+    app
+}
+
+fn collision_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("laser_vs_enemy");
+    group.sample_size(10);
+
+    for count in [500, 2000, 5000] {
+        group.bench_with_input(BenchmarkId::new("naive", count), &count, |b, &count| {
+            let mut app = naive_app(count);
+            b.iter(|| app.update());
+        });
+        group.bench_with_input(BenchmarkId::new("grid", count), &count, |b, &count| {
+            let mut app = grid_app(count);
+            b.iter(|| app.update());
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, collision_benchmark);
+criterion_main!(benches);
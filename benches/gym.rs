@@ -0,0 +1,49 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rust_invaders::gym::{Action, Env, EnvConfig, ObservationKind};
+
+const TICKS: usize = 1000;
+
+/// Steps of a whole second of game time, over and over: should stay well under a second
+fn gym_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("gym_1000_ticks");
+    group.sample_size(10);
+
+    let observations = [
+        ("features", ObservationKind::Features),
+        (
+            "raster",
+            ObservationKind::Raster {
+                width: 84,
+                height: 84,
+            },
+        ),
+    ];
+    for (name, observation) in observations {
+        group.bench_with_input(
+            BenchmarkId::new(name, TICKS),
+            &observation,
+            |b, &observation| {
+                let mut env = Env::new(EnvConfig {
+                    observation,
+                    ..Default::default()
+                });
+                let mut seed = 0;
+                env.reset(seed);
+                b.iter(|| {
+                    for tick in 0..TICKS {
+                        let step = env.step(Action::ALL[tick % Action::ALL.len()]);
+                        if step.done {
+                            seed += 1;
+                            env.reset(seed);
+                        }
+                    }
+                });
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, gym_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,318 @@
+use crate::{playing, TIME_STEP};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Plugin - plays the `SpriteAnimation` of sprite sheets, on game time
+pub struct AnimationPlugin;
+
+impl Plugin for AnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<AnimationFinished>().add_system_set(
+            SystemSet::new()
+                .with_run_criteria(playing)
+                .with_system(sprite_animation_system.label(AnimationSystem)),
+        );
+    }
+}
+
+/// Label - `AnimationFinished` subscribers run after
+#[derive(Debug, Clone, PartialEq, Eq, Hash, SystemLabel)]
+pub struct AnimationSystem;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AnimationMode {
+    /// back to the first frame after the last one
+    Loop,
+    /// stops after showing the last frame, then the animation is finished
+    Once,
+    /// back and forth between the first and the last frames
+    PingPong,
+}
+
+/// Frames `first..=last` of a sprite sheet, played at `fps`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "ClipFrames")]
+pub struct AnimationClip {
+    pub first: usize,
+    pub last: usize,
+    pub fps: f32,
+    pub mode: AnimationMode,
+}
+
+/// What an `AnimationClip` is loaded from, checked before it is played
+#[derive(Deserialize)]
+struct ClipFrames {
+    first: usize,
+    last: usize,
+    fps: f32,
+    mode: AnimationMode,
+}
+
+impl TryFrom<ClipFrames> for AnimationClip {
+    type Error = String;
+
+    fn try_from(frames: ClipFrames) -> Result<Self, Self::Error> {
+        if frames.last < frames.first {
+            return Err(format!(
+                "clip ends on frame {} before its first frame {}",
+                frames.last, frames.first
+            ));
+        }
+        if frames.fps <= 0. {
+            return Err(format!("clip plays at {} fps", frames.fps));
+        }
+        Ok(Self::new(
+            frames.first,
+            frames.last,
+            frames.fps,
+            frames.mode,
+        ))
+    }
+}
+
+impl AnimationClip {
+    pub fn new(first: usize, last: usize, fps: f32, mode: AnimationMode) -> Self {
+        Self {
+            first,
+            last: last.max(first),
+            fps,
+            mode,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.last - self.first + 1
+    }
+}
+
+/// What becomes of the entity once its `Once` clip finished
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnFinished {
+    /// holds the last frame
+    Stop,
+    Despawn,
+}
+
+/// Component - plays a clip on the `TextureAtlasSprite` of the entity
+#[derive(Component, Debug, Clone)]
+pub struct SpriteAnimation {
+    clip: AnimationClip,
+    pub on_finished: OnFinished,
+    timer: Timer,
+    frame: usize, // within the clip
+    backwards: bool,
+    finished: bool,
+}
+
+impl SpriteAnimation {
+    pub fn new(clip: AnimationClip) -> Self {
+        let timer = Timer::from_seconds(1. / clip.fps.max(f32::EPSILON), true);
+        Self {
+            clip,
+            on_finished: OnFinished::Stop,
+            timer,
+            frame: 0,
+            backwards: false,
+            finished: false,
+        }
+    }
+
+    pub fn despawn_when_finished(mut self) -> Self {
+        self.on_finished = OnFinished::Despawn;
+        self
+    }
+
+    /// Switches to another clip, from its start
+    pub fn play(&mut self, clip: AnimationClip) {
+        *self = Self {
+            on_finished: self.on_finished,
+            ..Self::new(clip)
+        };
+    }
+
+    pub fn clip(&self) -> &AnimationClip {
+        &self.clip
+    }
+
+    /// Index in the sprite sheet of the frame shown
+    pub fn index(&self) -> usize {
+        self.clip.first + self.frame
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Plays `seconds` of the clip, returns whether it finished on the way
+    fn advance(&mut self, seconds: f32) -> bool {
+        if self.finished {
+            return false;
+        }
+
+        self.timer.tick(Duration::from_secs_f32(seconds));
+        for _ in 0..self.timer.times_finished() {
+            self.next_frame();
+            if self.finished {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn next_frame(&mut self) {
+        let last = self.clip.len() - 1;
+        match self.clip.mode {
+            AnimationMode::Loop => self.frame = (self.frame + 1) % (last + 1),
+            AnimationMode::Once if self.frame == last => self.finished = true,
+            AnimationMode::Once => self.frame += 1,
+            AnimationMode::PingPong if last == 0 => {}
+            AnimationMode::PingPong => {
+                if self.frame == last {
+                    self.backwards = true;
+                } else if self.frame == 0 {
+                    self.backwards = false;
+                }
+                if self.backwards {
+                    self.frame -= 1;
+                } else {
+                    self.frame += 1;
+                }
+            }
+        }
+    }
+}
+
+/// Event - the `Once` clip of `entity` finished
+#[derive(Debug, Clone, Copy)]
+pub struct AnimationFinished {
+    pub entity: Entity,
+}
+
+fn sprite_animation_system(
+    mut commands: Commands,
+    mut finished: EventWriter<AnimationFinished>,
+    mut query: Query<(Entity, &mut SpriteAnimation, &mut TextureAtlasSprite)>,
+) {
+    for (entity, mut animation, mut sprite) in query.iter_mut() {
+        if animation.advance(TIME_STEP) {
+            finished.send(AnimationFinished { entity });
+            if animation.on_finished == OnFinished::Despawn {
+                commands.entity(entity).despawn();
+                continue;
+            }
+        }
+
+        let index = animation.index();
+        if sprite.index != index {
+            sprite.index = index;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::event::Events;
+
+    /// Sheet indices shown over `frames` frames of the clip, from the start
+    fn played(clip: AnimationClip, frames: usize) -> Vec<usize> {
+        let seconds = 1. / clip.fps;
+        let mut animation = SpriteAnimation::new(clip);
+        let mut indices = vec![animation.index()];
+        for _ in 1..frames {
+            animation.advance(seconds);
+            indices.push(animation.index());
+        }
+        indices
+    }
+
+    #[test]
+    fn clips_are_checked_on_load() {
+        let clip: AnimationClip =
+            ron::from_str("(first: 2, last: 5, fps: 10., mode: Loop)").unwrap();
+        assert_eq!(clip, AnimationClip::new(2, 5, 10., AnimationMode::Loop));
+
+        assert!(
+            ron::from_str::<AnimationClip>("(first: 5, last: 2, fps: 10., mode: Loop)").is_err()
+        );
+        assert!(
+            ron::from_str::<AnimationClip>("(first: 0, last: 2, fps: 0., mode: Once)").is_err()
+        );
+    }
+
+    #[test]
+    fn modes_walk_the_frames() {
+        let clip = |mode| AnimationClip::new(4, 6, 10., mode);
+        assert_eq!(
+            played(clip(AnimationMode::Loop), 7),
+            vec![4, 5, 6, 4, 5, 6, 4]
+        );
+        assert_eq!(played(clip(AnimationMode::Once), 5), vec![4, 5, 6, 6, 6]);
+        assert_eq!(
+            played(clip(AnimationMode::PingPong), 8),
+            vec![4, 5, 6, 5, 4, 5, 6, 5]
+        );
+        assert_eq!(
+            played(AnimationClip::new(2, 2, 10., AnimationMode::PingPong), 3),
+            vec![2, 2, 2]
+        );
+    }
+
+    #[test]
+    fn once_finishes_after_its_last_frame_at_the_clip_fps() {
+        let mut animation =
+            SpriteAnimation::new(AnimationClip::new(0, 2, 20., AnimationMode::Once));
+        // 3 frames of 0.05 s
+        assert!(!animation.advance(0.14));
+        assert_eq!(animation.index(), 2);
+        assert!(animation.advance(0.02));
+        assert!(animation.is_finished());
+        // only once
+        assert!(!animation.advance(1.));
+        assert_eq!(animation.index(), 2);
+
+        animation.play(AnimationClip::new(5, 7, 20., AnimationMode::Loop));
+        assert!(!animation.is_finished());
+        assert_eq!(animation.index(), 5);
+    }
+
+    #[test]
+    fn finished_animations_send_an_event_and_despawn() {
+        let mut app = App::new();
+        app.add_event::<AnimationFinished>()
+            .add_system(sprite_animation_system);
+        let clip = |mode| AnimationClip::new(1, 2, 1. / TIME_STEP, mode);
+        let despawned = app
+            .world
+            .spawn()
+            .insert(TextureAtlasSprite::default())
+            .insert(SpriteAnimation::new(clip(AnimationMode::Once)).despawn_when_finished())
+            .id();
+        let stopped = app
+            .world
+            .spawn()
+            .insert(TextureAtlasSprite::default())
+            .insert(SpriteAnimation::new(clip(AnimationMode::Once)))
+            .id();
+
+        // a frame per update, give or take rounding
+        for _ in 0..3 {
+            app.update();
+        }
+
+        let events = app.world.resource::<Events<AnimationFinished>>();
+        let mut finished: Vec<Entity> = events
+            .get_reader()
+            .iter(events)
+            .map(|event| event.entity)
+            .collect();
+        finished.sort();
+        assert_eq!(finished, vec![despawned, stopped]);
+        assert!(app.world.get_entity(despawned).is_none());
+        assert_eq!(
+            app.world.get::<TextureAtlasSprite>(stopped).unwrap().index,
+            2
+        );
+    }
+}
@@ -0,0 +1,418 @@
+use crate::GameState;
+use bevy::audio::AudioSink;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+//region --Sound Assets
+
+const SOUNDS: [(Sound, &str); 5] = [
+    (Sound::PlayerLaser, "sounds/laser_a.wav"),
+    (Sound::EnemyLaser, "sounds/laser_b.wav"),
+    (Sound::Hit, "sounds/hit.wav"),
+    (Sound::Explosion, "sounds/explosion.wav"),
+    (Sound::Respawn, "sounds/respawn.wav"),
+];
+
+const MUSIC: [(Music, &str); 3] = [
+    (Music::Title, "sounds/music_title.wav"),
+    (Music::Game, "sounds/music_game.wav"),
+    (Music::GameOver, "sounds/music_game_over.wav"),
+];
+
+//endregion --Sound Assets
+
+/// Plugin - turns `PlaySound` events and the `CurrentMusic` resource into audio,
+/// through whatever `SoundOutput` backend the app was given (silent by default)
+pub struct SoundPlugin;
+
+impl Plugin for SoundPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<SoundOutput>() {
+            app.insert_resource(SoundOutput::new(NullBackend));
+        }
+
+        app.init_resource::<AudioSettings>()
+            .insert_resource(CurrentMusic(None))
+            .add_event::<PlaySound>()
+            .add_system(audio_keyboard_system)
+            .add_system_to_stage(CoreStage::PostUpdate, sound_system)
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                state_music_system.before(music_system),
+            )
+            .add_system_to_stage(CoreStage::PostUpdate, music_system);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Sound {
+    PlayerLaser,
+    EnemyLaser,
+    Hit,
+    Explosion,
+    Respawn,
+}
+
+/// Background music of each `GameState`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Music {
+    /// title, settings and high score screens
+    Title,
+    /// playing or paused
+    Game,
+    GameOver,
+}
+
+impl Music {
+    pub fn of(state: GameState) -> Self {
+        match state {
+            GameState::Title | GameState::Settings | GameState::HighScores => Music::Title,
+            GameState::Playing | GameState::Paused => Music::Game,
+            GameState::GameOver => Music::GameOver,
+        }
+    }
+}
+
+/// Event - play a sound effect once
+pub struct PlaySound(pub Sound);
+
+/// Resource - background music that should be playing, `None` for silence.
+/// Follows the `GameState` once there is one.
+pub struct CurrentMusic(pub Option<Music>);
+
+/// Resource - volumes are in 0. -> 1.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioSettings {
+    pub volume: f32,
+    pub music_volume: f32,
+    pub muted: bool,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            volume: 0.8,
+            music_volume: 0.5,
+            muted: false,
+        }
+    }
+}
+
+impl AudioSettings {
+    pub fn effective_volume(&self) -> f32 {
+        if self.muted {
+            0.
+        } else {
+            self.volume
+        }
+    }
+
+    pub fn effective_music_volume(&self) -> f32 {
+        if self.muted {
+            0.
+        } else {
+            self.music_volume
+        }
+    }
+}
+
+/// What a backend may need from the app to make noise. Every field is optional
+/// so the same systems run in headless apps without audio or asset plugins.
+pub struct AudioContext<'a> {
+    pub audio: Option<&'a Audio>,
+    pub sinks: Option<&'a Assets<AudioSink>>,
+    pub asset_server: Option<&'a AssetServer>,
+}
+
+/// Where sounds end up. Swap it through `SoundOutput` to silence or record the game.
+pub trait AudioBackend: Send + Sync {
+    fn play(&mut self, context: &AudioContext, sound: Sound, volume: f32);
+    fn play_music(&mut self, context: &AudioContext, music: Music, volume: f32);
+    fn set_music_volume(&mut self, context: &AudioContext, volume: f32);
+    fn stop_music(&mut self, context: &AudioContext);
+}
+
+/// Resource - the audio backend in use
+pub struct SoundOutput(pub Box<dyn AudioBackend>);
+
+impl SoundOutput {
+    pub fn new(backend: impl AudioBackend + 'static) -> Self {
+        Self(Box::new(backend))
+    }
+}
+
+/// Plays through bevy's audio plugin, loading the sound assets on first use
+#[derive(Default)]
+pub struct BevyAudioBackend {
+    sounds: HashMap<Sound, Handle<AudioSource>>,
+    music: HashMap<Music, Handle<AudioSource>>,
+    music_sink: Option<Handle<AudioSink>>,
+}
+
+impl BevyAudioBackend {
+    fn load(&mut self, context: &AudioContext) {
+        if let (true, Some(asset_server)) = (self.sounds.is_empty(), context.asset_server) {
+            for (sound, path) in SOUNDS {
+                self.sounds.insert(sound, asset_server.load(path));
+            }
+            for (music, path) in MUSIC {
+                self.music.insert(music, asset_server.load(path));
+            }
+        }
+    }
+}
+
+impl AudioBackend for BevyAudioBackend {
+    fn play(&mut self, context: &AudioContext, sound: Sound, volume: f32) {
+        self.load(context);
+        if let (Some(audio), Some(handle)) = (context.audio, self.sounds.get(&sound)) {
+            audio.play_with_settings(handle.clone(), PlaybackSettings::ONCE.with_volume(volume));
+        }
+    }
+
+    fn play_music(&mut self, context: &AudioContext, music: Music, volume: f32) {
+        self.stop_music(context);
+        self.load(context);
+        if let (Some(audio), Some(sinks), Some(handle)) =
+            (context.audio, context.sinks, self.music.get(&music))
+        {
+            let sink = audio
+                .play_with_settings(handle.clone(), PlaybackSettings::LOOP.with_volume(volume));
+            // keep a strong handle, or the sink can't be controlled later
+            self.music_sink = Some(sinks.get_handle(sink));
+        }
+    }
+
+    fn set_music_volume(&mut self, context: &AudioContext, volume: f32) {
+        if let (Some(sinks), Some(handle)) = (context.sinks, &self.music_sink) {
+            if let Some(sink) = sinks.get(handle) {
+                sink.set_volume(volume);
+            }
+        }
+    }
+
+    fn stop_music(&mut self, context: &AudioContext) {
+        if let (Some(sinks), Some(handle)) = (context.sinks, self.music_sink.take()) {
+            if let Some(sink) = sinks.get(handle) {
+                sink.stop();
+            }
+        }
+    }
+}
+
+/// Plays nothing
+pub struct NullBackend;
+
+impl AudioBackend for NullBackend {
+    fn play(&mut self, _: &AudioContext, _: Sound, _: f32) {}
+    fn play_music(&mut self, _: &AudioContext, _: Music, _: f32) {}
+    fn set_music_volume(&mut self, _: &AudioContext, _: f32) {}
+    fn stop_music(&mut self, _: &AudioContext) {}
+}
+
+/// Plays nothing but keeps a log of every sound that would have been heard.
+/// Clones share the same log, so keep one to inspect it after handing the other to the app.
+#[derive(Clone, Default)]
+pub struct RecordingBackend {
+    pub sounds: Arc<Mutex<Vec<Sound>>>,
+    pub music: Arc<Mutex<Option<Music>>>,
+}
+
+impl RecordingBackend {
+    pub fn sounds(&self) -> Vec<Sound> {
+        self.sounds.lock().unwrap().clone()
+    }
+
+    pub fn music(&self) -> Option<Music> {
+        *self.music.lock().unwrap()
+    }
+}
+
+impl AudioBackend for RecordingBackend {
+    fn play(&mut self, _: &AudioContext, sound: Sound, volume: f32) {
+        if volume > 0. {
+            self.sounds.lock().unwrap().push(sound);
+        }
+    }
+
+    fn play_music(&mut self, _: &AudioContext, music: Music, _: f32) {
+        *self.music.lock().unwrap() = Some(music);
+    }
+
+    fn set_music_volume(&mut self, _: &AudioContext, _: f32) {}
+
+    fn stop_music(&mut self, _: &AudioContext) {
+        *self.music.lock().unwrap() = None;
+    }
+}
+
+fn audio_keyboard_system(
+    keyboard: Option<Res<Input<KeyCode>>>,
+    mut settings: ResMut<AudioSettings>,
+) {
+    if let Some(keyboard) = keyboard {
+        if keyboard.just_pressed(KeyCode::M) {
+            settings.muted = !settings.muted;
+        }
+    }
+}
+
+fn sound_system(
+    mut output: ResMut<SoundOutput>,
+    settings: Res<AudioSettings>,
+    mut events: EventReader<PlaySound>,
+    audio: Option<Res<Audio>>,
+    sinks: Option<Res<Assets<AudioSink>>>,
+    asset_server: Option<Res<AssetServer>>,
+) {
+    let context = AudioContext {
+        audio: audio.as_deref(),
+        sinks: sinks.as_deref(),
+        asset_server: asset_server.as_deref(),
+    };
+
+    let volume = settings.effective_volume();
+    for PlaySound(sound) in events.iter() {
+        if volume > 0. {
+            output.0.play(&context, *sound, volume);
+        }
+    }
+}
+
+/// Switches tracks with the game state, a track going on over states that share it
+fn state_music_system(state: Option<Res<GameState>>, mut music: ResMut<CurrentMusic>) {
+    if let Some(state) = state.filter(|state| state.is_changed()) {
+        let track = Some(Music::of(*state));
+        if music.0 != track {
+            music.0 = track;
+        }
+    }
+}
+
+fn music_system(
+    mut output: ResMut<SoundOutput>,
+    settings: Res<AudioSettings>,
+    music: Res<CurrentMusic>,
+    audio: Option<Res<Audio>>,
+    sinks: Option<Res<Assets<AudioSink>>>,
+    asset_server: Option<Res<AssetServer>>,
+) {
+    let context = AudioContext {
+        audio: audio.as_deref(),
+        sinks: sinks.as_deref(),
+        asset_server: asset_server.as_deref(),
+    };
+
+    if music.is_changed() {
+        match music.0 {
+            Some(track) => output
+                .0
+                .play_music(&context, track, settings.effective_music_volume()),
+            None => output.0.stop_music(&context),
+        }
+    } else if settings.is_changed() {
+        output
+            .0
+            .set_music_volume(&context, settings.effective_music_volume());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enemy::spawn_custom_formation;
+    use crate::settings::Settings;
+    use crate::{headless_app, start_run, GodMode, Player, PlayerIntent, TickSystem};
+    use bevy::ecs::event::Events;
+
+    fn app_with(recording: &RecordingBackend) -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(SoundOutput::new(recording.clone()))
+            .add_plugin(SoundPlugin);
+        app
+    }
+
+    #[test]
+    fn records_played_sounds_and_music() {
+        let recording = RecordingBackend::default();
+        let mut app = app_with(&recording);
+
+        app.world.resource_mut::<CurrentMusic>().0 = Some(Music::Game);
+        app.world
+            .resource_mut::<Events<PlaySound>>()
+            .send(PlaySound(Sound::PlayerLaser));
+        app.world
+            .resource_mut::<Events<PlaySound>>()
+            .send(PlaySound(Sound::Explosion));
+        app.update();
+
+        assert_eq!(
+            recording.sounds(),
+            vec![Sound::PlayerLaser, Sound::Explosion]
+        );
+        assert_eq!(recording.music(), Some(Music::Game));
+    }
+
+    #[test]
+    fn muted_sounds_are_not_played() {
+        let recording = RecordingBackend::default();
+        let mut app = app_with(&recording);
+
+        app.world.resource_mut::<AudioSettings>().muted = true;
+        app.world
+            .resource_mut::<Events<PlaySound>>()
+            .send(PlaySound(Sound::Hit));
+        app.update();
+
+        assert!(recording.sounds().is_empty());
+    }
+
+    fn fire_system(mut intent: ResMut<PlayerIntent>) {
+        intent.fire = true;
+    }
+
+    #[test]
+    fn game_events_make_their_sounds() {
+        let recording = RecordingBackend::default();
+        let mut app = headless_app(Settings::default(), 1, 1);
+        app.insert_resource(SoundOutput::new(recording.clone()))
+            .insert_resource(GodMode(true))
+            .add_system_to_stage(CoreStage::PreUpdate, fire_system.after(TickSystem::Intent));
+        start_run(&mut app);
+
+        let mut players = app.world.query_filtered::<&Transform, With<Player>>();
+        let player = loop {
+            app.update();
+            if let Some(transform) = players.iter(&app.world).next() {
+                break transform.translation;
+            }
+        };
+        assert_eq!(recording.sounds().first(), Some(&Sound::Respawn));
+        assert_eq!(recording.music(), Some(Music::Game));
+
+        // a still enemy right above the player's guns
+        let above = Vec2::new(player.x, player.y + 150.);
+        spawn_custom_formation(&mut app.world, above, above, Vec2::ZERO, 1);
+        for _ in 0..60 {
+            app.update();
+        }
+
+        let sounds = recording.sounds();
+        let first = |sound| sounds.iter().position(|played| *played == sound);
+        let (fired, hit, exploded) = (
+            first(Sound::PlayerLaser).expect("fired"),
+            first(Sound::Hit).expect("hit"),
+            first(Sound::Explosion).expect("exploded"),
+        );
+        assert!(fired < hit && hit < exploded);
+
+        // and the game over track once the run is over
+        *app.world.resource_mut::<GameState>() = GameState::GameOver;
+        app.update();
+        assert_eq!(recording.music(), Some(Music::GameOver));
+    }
+}
@@ -0,0 +1,260 @@
+use crate::components::{Enemy, FromEnemy, Laser, Player, Velocity};
+use crate::{
+    playing, PlayerIntent, TickSystem, WinSize, BASE_SPEED, ENEMY_LASER_SIZE, ENEMY_SIZE,
+    PLAYER_SIZE, SPRITE_SCALE, TIME_STEP,
+};
+use bevy::prelude::*;
+
+/// Ticks ahead an enemy laser is dodged
+const DANGER_HORIZON: f32 = 45.;
+/// Room kept between the player and a threat, beyond where they touch
+const SAFETY_MARGIN: f32 = 20.;
+/// Enemies closer than this to the player's row are dodged like lasers
+const RAM_DISTANCE: f32 = 160.;
+/// Off center an enemy can be and still be fired at
+const AIM_TOLERANCE: f32 = 30.;
+/// Ticks between two presses of fire
+const FIRE_COOLDOWN: u32 = 12;
+const EDGE_MARGIN: f32 = 10.;
+/// Spots across the field the bot considers moving to
+const CANDIDATES: usize = 64;
+
+/// Plugin - a bot playing in place of the keyboard while `Autopilot` is on
+pub struct AutopilotPlugin;
+
+impl Plugin for AutopilotPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Autopilot>().add_system_set_to_stage(
+            CoreStage::PreUpdate,
+            SystemSet::new().with_run_criteria(playing).with_system(
+                autopilot_system
+                    .label(AutopilotSystem)
+                    .after(TickSystem::Intent),
+            ),
+        );
+    }
+}
+
+/// Label - the autopilot overwrites the keyboard's intent, recorders run after it
+#[derive(Debug, Clone, PartialEq, Eq, Hash, SystemLabel)]
+pub struct AutopilotSystem;
+
+/// Resource - the bot, when enabled
+#[derive(Debug, Clone, Default)]
+pub struct Autopilot {
+    pub enabled: bool,
+    cooldown: u32, // ticks before fire can be pressed again
+}
+
+/// What the bot sees of the field, positions in pixels and velocities in pixels per tick
+pub struct Field {
+    pub half_width: f32,
+    pub player: Vec2,
+    pub enemies: Vec<Vec2>,
+    pub enemy_lasers: Vec<(Vec2, Vec2)>,
+}
+
+impl Autopilot {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            ..default()
+        }
+    }
+
+    /// Lines up under the closest enemy and fires when it is right above,
+    /// as long as the way there is clear of what would hit the player soon
+    pub fn decide(&mut self, field: &Field) -> PlayerIntent {
+        self.cooldown = self.cooldown.saturating_sub(1);
+
+        let player = field.player;
+        let speed = BASE_SPEED * TIME_STEP;
+        let half_player = PLAYER_SIZE.0 / 2. * SPRITE_SCALE;
+        let reach = field.half_width - half_player - EDGE_MARGIN;
+
+        // lasers that will cross the player's row, and enemies about to ram it
+        let laser_width = half_player + ENEMY_LASER_SIZE.0 / 2. * SPRITE_SCALE + SAFETY_MARGIN;
+        let laser_height = (PLAYER_SIZE.1 + ENEMY_LASER_SIZE.1) / 2. * SPRITE_SCALE;
+        let crossings = field
+            .enemy_lasers
+            .iter()
+            .filter_map(|(position, velocity)| {
+                if velocity.y >= 0. {
+                    return None;
+                }
+                let dy = position.y - player.y;
+                let enter = (dy - laser_height) / -velocity.y;
+                let exit = (dy + laser_height) / -velocity.y;
+                (exit >= 0. && enter <= DANGER_HORIZON).then(|| Threat {
+                    x: position.x + velocity.x * enter.max(0.),
+                    width: laser_width,
+                    enter,
+                    exit,
+                })
+            });
+        let enemy_width = half_player + ENEMY_SIZE.0 / 2. * SPRITE_SCALE + SAFETY_MARGIN;
+        let rams = field
+            .enemies
+            .iter()
+            .filter(|enemy| (enemy.y - player.y).abs() < RAM_DISTANCE)
+            .map(|enemy| Threat {
+                x: enemy.x,
+                width: enemy_width,
+                enter: 0.,
+                exit: DANGER_HORIZON,
+            });
+        let threats: Vec<Threat> = crossings.chain(rams).collect();
+
+        let aimed = field
+            .enemies
+            .iter()
+            .filter(|enemy| enemy.y > player.y)
+            .min_by(|a, b| (a.x - player.x).abs().total_cmp(&(b.x - player.x).abs()));
+        let wanted = aimed.map_or(player.x, |enemy| enemy.x).clamp(-reach, reach);
+
+        // the safe spot closest to where the player wants to be, staying put if there is none
+        let target = (0..=CANDIDATES)
+            .map(|i| -reach + 2. * reach * i as f32 / CANDIDATES as f32)
+            .chain([wanted])
+            .filter(|x| {
+                threats
+                    .iter()
+                    .all(|threat| threat.misses(player.x, *x, speed))
+            })
+            .min_by(|a, b| (a - wanted).abs().total_cmp(&(b - wanted).abs()))
+            .unwrap_or(player.x);
+
+        let fire = self.cooldown == 0
+            && aimed.is_some_and(|enemy| (enemy.x - player.x).abs() < AIM_TOLERANCE);
+        if fire {
+            self.cooldown = FIRE_COOLDOWN;
+        }
+
+        PlayerIntent {
+            left: target < player.x - speed / 2.,
+            right: target > player.x + speed / 2.,
+            fire,
+        }
+    }
+}
+
+/// Something crossing the player's row between `enter` and `exit` ticks from now,
+/// dangerous closer than `width` to `x`
+struct Threat {
+    x: f32,
+    width: f32,
+    enter: f32,
+    exit: f32,
+}
+
+impl Threat {
+    /// Whether moving from `from` to `to` and staying there keeps clear of it
+    fn misses(&self, from: f32, to: f32, speed: f32) -> bool {
+        let (low, high) = (self.x - self.width, self.x + self.width);
+        let (near, far) = if from <= to {
+            (low - from, high - from)
+        } else {
+            (from - high, from - low)
+        };
+        let distance = (to - from).abs();
+        if far < 0. || near > distance {
+            return true; // not on the way
+        }
+
+        // ticks spent within its reach on the way, forever if the move ends there
+        let inside = near.max(0.) / speed;
+        let outside = if far < distance {
+            far / speed
+        } else {
+            f32::INFINITY
+        };
+        outside < self.enter || inside > self.exit
+    }
+}
+
+fn autopilot_system(
+    mut autopilot: ResMut<Autopilot>,
+    mut intent: ResMut<PlayerIntent>,
+    win_size: Res<WinSize>,
+    player_query: Query<&Transform, With<Player>>,
+    enemy_query: Query<&Transform, With<Enemy>>,
+    laser_query: Query<(&Transform, &Velocity), (With<Laser>, With<FromEnemy>)>,
+) {
+    if !autopilot.enabled {
+        return;
+    }
+
+    let player = match player_query.get_single() {
+        Ok(transform) => transform.translation.truncate(),
+        Err(_) => {
+            *intent = PlayerIntent::default();
+            return;
+        }
+    };
+
+    let field = Field {
+        half_width: win_size.width / 2.,
+        player,
+        enemies: enemy_query
+            .iter()
+            .map(|transform| transform.translation.truncate())
+            .collect(),
+        enemy_lasers: laser_query
+            .iter()
+            .map(|(transform, velocity)| {
+                let velocity = Vec2::new(velocity.x, velocity.y) * BASE_SPEED * TIME_STEP;
+                (transform.translation.truncate(), velocity)
+            })
+            .collect(),
+    };
+    *intent = autopilot.decide(&field);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(enemies: Vec<Vec2>, enemy_lasers: Vec<(Vec2, Vec2)>) -> Field {
+        Field {
+            half_width: 299.,
+            player: Vec2::new(0., -314.),
+            enemies,
+            enemy_lasers,
+        }
+    }
+
+    #[test]
+    fn fires_at_the_enemy_above() {
+        let mut autopilot = Autopilot::new(true);
+        let intent = autopilot.decide(&field(vec![Vec2::new(3., 200.)], vec![]));
+        assert!(intent.fire);
+        assert!(!intent.left && !intent.right);
+
+        // then waits before pressing fire again
+        let intent = autopilot.decide(&field(vec![Vec2::new(3., 200.)], vec![]));
+        assert!(!intent.fire);
+    }
+
+    #[test]
+    fn moves_under_the_closest_enemy() {
+        let mut autopilot = Autopilot::new(true);
+        let intent = autopilot.decide(&field(
+            vec![Vec2::new(-150., 200.), Vec2::new(250., 200.)],
+            vec![],
+        ));
+        assert!(intent.left);
+    }
+
+    #[test]
+    fn dodges_a_falling_laser() {
+        let mut autopilot = Autopilot::new(true);
+        let laser = (Vec2::new(0., -250.), Vec2::new(0., -4.));
+        let intent = autopilot.decide(&field(vec![], vec![laser]));
+        assert!(intent.left || intent.right);
+
+        // a laser already past the player is no threat
+        let laser = (Vec2::new(0., -400.), Vec2::new(0., -4.));
+        let intent = autopilot.decide(&field(vec![], vec![laser]));
+        assert!(!intent.left && !intent.right);
+    }
+}
@@ -0,0 +1,402 @@
+//! Balance report: many headless runs played by the autopilot, at every difficulty
+//! and over a spread of seeds, summed up so changes to the game can be compared.
+
+use crate::autopilot::Autopilot;
+use crate::damage::Death;
+use crate::difficulty::Difficulty;
+use crate::enemy::{Formation, FormationData, FormationId};
+use crate::settings::Settings;
+use crate::{
+    headless_app, start_run, DeathCause, GameClock, GameState, PlayerKilled, Score, Wave, TIME_STEP,
+};
+use bevy::ecs::event::{Events, ManualEventReader};
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use serde::Serialize;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Ranges each formation parameter is split into for the lethality table
+const BUCKETS: usize = 4;
+
+/// Which runs to play
+#[derive(Debug, Clone)]
+pub struct BalanceConfig {
+    pub difficulties: Vec<Difficulty>,
+    pub seeds: Vec<u64>,
+    /// Runs still going after this many ticks are stopped there
+    pub max_ticks: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BalanceReport {
+    pub runs: Vec<RunReport>,
+    pub difficulties: Vec<DifficultySummary>,
+    pub formations: Vec<FormationReport>,
+    pub lethality: Vec<LethalityBucket>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RunReport {
+    pub difficulty: Difficulty,
+    pub seed: u64,
+    pub ticks: u64,
+    pub seconds: f64,
+    pub game_over: bool,
+    pub score: u32,
+    pub wave: u32,
+    pub kills: u32,
+    pub kills_per_minute: f64,
+    pub deaths_by_laser: u32,
+    pub deaths_by_ram: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DifficultySummary {
+    pub difficulty: Difficulty,
+    pub runs: usize,
+    pub game_overs: usize,
+    pub mean_seconds: f64,
+    pub min_seconds: f64,
+    pub max_seconds: f64,
+    pub kills_per_minute: f64,
+    pub deaths_by_laser: u32,
+    pub deaths_by_ram: u32,
+}
+
+/// A formation met during a run, with the lives it took
+#[derive(Debug, Clone, Serialize)]
+pub struct FormationReport {
+    pub difficulty: Difficulty,
+    pub seed: u64,
+    pub id: u32,
+    pub members: u32,
+    pub radius_x: f32,
+    pub radius_y: f32,
+    pub pivot_x: f32,
+    pub pivot_y: f32,
+    pub speed: f32,
+    pub deaths: u32,
+}
+
+/// Lives taken by the formations whose `parameter` was within `from..to`, over all runs
+#[derive(Debug, Clone, Serialize)]
+pub struct LethalityBucket {
+    pub parameter: &'static str,
+    pub from: f32,
+    pub to: f32,
+    pub formations: u32,
+    pub deaths: u32,
+    pub deaths_per_formation: f64,
+}
+
+impl BalanceReport {
+    /// Plays every run of `config`, one after the other. `progress` is told about each one done.
+    pub fn play(config: &BalanceConfig, mut progress: impl FnMut(&RunReport)) -> Self {
+        let mut runs = Vec::new();
+        let mut formations = Vec::new();
+        for difficulty in config.difficulties.iter() {
+            for seed in config.seeds.iter() {
+                let (run, met) = play_run(*difficulty, *seed, config.max_ticks);
+                progress(&run);
+                runs.push(run);
+                formations.extend(met);
+            }
+        }
+
+        let difficulties = config
+            .difficulties
+            .iter()
+            .map(|difficulty| DifficultySummary::new(*difficulty, &runs))
+            .collect();
+        let lethality = lethality(&formations);
+
+        Self {
+            runs,
+            difficulties,
+            formations,
+            lethality,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    /// Writes `report.json`, and a CSV file of each table, into `dir`
+    pub fn save(&self, dir: &Path) -> Result<(), String> {
+        let write = |name: &str, text: String| {
+            let path = dir.join(name);
+            fs::write(&path, text)
+                .map_err(|err| format!("could not write {}: {}", path.display(), err))
+        };
+
+        fs::create_dir_all(dir)
+            .map_err(|err| format!("could not create {}: {}", dir.display(), err))?;
+        write("report.json", self.to_json())?;
+        write("runs.csv", to_csv(&self.runs))?;
+        write("difficulties.csv", to_csv(&self.difficulties))?;
+        write("formations.csv", to_csv(&self.formations))?;
+        write("lethality.csv", to_csv(&self.lethality))
+    }
+}
+
+impl DifficultySummary {
+    fn new(difficulty: Difficulty, runs: &[RunReport]) -> Self {
+        let runs: Vec<&RunReport> = runs
+            .iter()
+            .filter(|run| run.difficulty == difficulty)
+            .collect();
+        let seconds = runs.iter().map(|run| run.seconds);
+        let total_seconds: f64 = seconds.clone().sum();
+        let kills: u32 = runs.iter().map(|run| run.kills).sum();
+
+        Self {
+            difficulty,
+            runs: runs.len(),
+            game_overs: runs.iter().filter(|run| run.game_over).count(),
+            mean_seconds: total_seconds / runs.len().max(1) as f64,
+            min_seconds: seconds.clone().reduce(f64::min).unwrap_or_default(),
+            max_seconds: seconds.reduce(f64::max).unwrap_or_default(),
+            kills_per_minute: per_minute(kills, total_seconds),
+            deaths_by_laser: runs.iter().map(|run| run.deaths_by_laser).sum(),
+            deaths_by_ram: runs.iter().map(|run| run.deaths_by_ram).sum(),
+        }
+    }
+}
+
+fn per_minute(count: u32, seconds: f64) -> f64 {
+    if seconds > 0. {
+        count as f64 * 60. / seconds
+    } else {
+        0.
+    }
+}
+
+/// A headless run of the autopilot, until game over or `max_ticks`
+fn play_run(
+    difficulty: Difficulty,
+    seed: u64,
+    max_ticks: u64,
+) -> (RunReport, Vec<FormationReport>) {
+    let settings = Settings {
+        difficulty,
+        ..default()
+    };
+    let mut app = headless_app(settings, seed, 1);
+    app.insert_resource(Autopilot::new(true));
+    start_run(&mut app);
+
+    let mut formation_query = app.world.query::<&Formation>();
+    let mut deaths = ManualEventReader::<Death>::default();
+    let mut killed = ManualEventReader::<PlayerKilled>::default();
+    let mut met: HashMap<FormationId, (Arc<FormationData>, u32)> = HashMap::default();
+    let (mut kills, mut deaths_by_laser, mut deaths_by_ram) = (0, 0, 0);
+
+    for _ in 0..max_ticks {
+        app.update();
+        let world = &mut app.world;
+
+        for formation in formation_query.iter(world) {
+            met.entry(formation.data.id)
+                .or_insert_with(|| (formation.data.clone(), 0));
+        }
+
+        let events = world.resource::<Events<Death>>();
+        kills += deaths.iter(events).filter(|death| death.is_kill()).count() as u32;
+
+        for event in killed.iter(world.resource::<Events<PlayerKilled>>()) {
+            match event.cause {
+                DeathCause::Laser => deaths_by_laser += 1,
+                DeathCause::Ram => deaths_by_ram += 1,
+            }
+            if let Some(data) = &event.formation {
+                met.entry(data.id).or_insert_with(|| (data.clone(), 0)).1 += 1;
+            }
+        }
+
+        if *world.resource::<GameState>() != GameState::Playing {
+            break;
+        }
+    }
+
+    let ticks = app.world.resource::<GameClock>().tick;
+    let seconds = ticks as f64 / (1. / TIME_STEP as f64).round();
+    let run = RunReport {
+        difficulty,
+        seed,
+        ticks,
+        seconds,
+        game_over: *app.world.resource::<GameState>() == GameState::GameOver,
+        score: app.world.resource::<Score>().0,
+        wave: app.world.resource::<Wave>().0,
+        kills,
+        kills_per_minute: per_minute(kills, seconds),
+        deaths_by_laser,
+        deaths_by_ram,
+    };
+
+    let mut formations: Vec<FormationReport> = met
+        .into_iter()
+        .map(|(_, (data, deaths))| FormationReport {
+            difficulty,
+            seed,
+            id: data.id.0,
+            members: data.members,
+            radius_x: data.radius.0,
+            radius_y: data.radius.1,
+            pivot_x: data.pivot.0,
+            pivot_y: data.pivot.1,
+            speed: data.speed,
+            deaths,
+        })
+        .collect();
+    formations.sort_by_key(|formation| formation.id);
+
+    (run, formations)
+}
+
+/// Splits the range of each parameter into `BUCKETS`, and sums up the formations of each
+fn lethality(formations: &[FormationReport]) -> Vec<LethalityBucket> {
+    let parameters: [(&'static str, fn(&FormationReport) -> f32); 4] = [
+        ("radius_x", |formation| formation.radius_x),
+        ("pivot_x", |formation| formation.pivot_x),
+        ("pivot_y", |formation| formation.pivot_y),
+        ("speed", |formation| formation.speed),
+    ];
+
+    let mut buckets = Vec::new();
+    for (parameter, value) in parameters {
+        let values = formations.iter().map(value);
+        let (min, max) = match (values.clone().reduce(f32::min), values.reduce(f32::max)) {
+            (Some(min), Some(max)) => (min, max),
+            _ => continue,
+        };
+        let count = if max > min { BUCKETS } else { 1 };
+        let width = (max - min) / count as f32;
+
+        let first = buckets.len();
+        for i in 0..count {
+            buckets.push(LethalityBucket {
+                parameter,
+                from: min + width * i as f32,
+                to: if i + 1 == count {
+                    max
+                } else {
+                    min + width * (i + 1) as f32
+                },
+                formations: 0,
+                deaths: 0,
+                deaths_per_formation: 0.,
+            });
+        }
+        for formation in formations {
+            let i = if width > 0. {
+                (((value(formation) - min) / width) as usize).min(count - 1)
+            } else {
+                0
+            };
+            let bucket = &mut buckets[first + i];
+            bucket.formations += 1;
+            bucket.deaths += formation.deaths;
+        }
+    }
+
+    for bucket in buckets.iter_mut() {
+        bucket.deaths_per_formation = bucket.deaths as f64 / bucket.formations.max(1) as f64;
+    }
+    buckets
+}
+
+/// Rows of `rows` under a header of their field names, through their JSON form
+fn to_csv<T: Serialize>(rows: &[T]) -> String {
+    let mut text = String::new();
+    for (i, row) in rows.iter().enumerate() {
+        let fields = match serde_json::to_value(row) {
+            Ok(serde_json::Value::Object(fields)) => fields,
+            _ => continue,
+        };
+        if i == 0 {
+            let header: Vec<&str> = fields.keys().map(String::as_str).collect();
+            let _ = writeln!(text, "{}", header.join(","));
+        }
+        let values: Vec<String> = fields
+            .values()
+            .map(|value| match value {
+                serde_json::Value::String(value) => value.clone(),
+                value => value.to_string(),
+            })
+            .collect();
+        let _ = writeln!(text, "{}", values.join(","));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn formation(radius_x: f32, deaths: u32) -> FormationReport {
+        FormationReport {
+            difficulty: Difficulty::Normal,
+            seed: 1,
+            id: 0,
+            members: 2,
+            radius_x,
+            radius_y: 100.,
+            pivot_x: 0.,
+            pivot_y: 50.,
+            speed: 500.,
+            deaths,
+        }
+    }
+
+    #[test]
+    fn lethality_buckets_split_the_range() {
+        let formations = [
+            formation(80., 2),
+            formation(90., 0),
+            formation(140., 1),
+            formation(150., 1),
+        ];
+        let buckets = lethality(&formations);
+
+        let radius: Vec<_> = buckets
+            .iter()
+            .filter(|bucket| bucket.parameter == "radius_x")
+            .map(|bucket| (bucket.formations, bucket.deaths))
+            .collect();
+        assert_eq!(radius, vec![(2, 2), (0, 0), (0, 0), (2, 2)]);
+
+        // the same speed everywhere makes a single bucket
+        let speed: Vec<_> = buckets
+            .iter()
+            .filter(|bucket| bucket.parameter == "speed")
+            .collect();
+        assert_eq!(speed.len(), 1);
+        assert_eq!(speed[0].deaths_per_formation, 1.);
+    }
+
+    #[test]
+    fn csv_has_a_header_and_a_row_per_run() {
+        let report = BalanceReport::play(
+            &BalanceConfig {
+                difficulties: vec![Difficulty::Hard],
+                seeds: vec![1],
+                max_ticks: 300,
+            },
+            |_| {},
+        );
+        assert_eq!(report.runs.len(), 1);
+        assert_eq!(report.runs[0].ticks, 300);
+        assert!(!report.formations.is_empty());
+
+        let csv = to_csv(&report.runs);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("difficulty,seed,ticks"));
+        assert!(lines[1].starts_with("Hard,1,300"));
+    }
+}
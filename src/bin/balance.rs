@@ -0,0 +1,72 @@
+use clap::Parser;
+use rust_invaders::balance::{BalanceConfig, BalanceReport};
+use rust_invaders::difficulty::Difficulty;
+use std::path::PathBuf;
+
+/// Balance report - plays many headless runs with the autopilot at each difficulty,
+/// and writes their survival times, kill rates, deaths by cause and the most lethal
+/// formation parameters as CSV and JSON
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Args {
+    /// Difficulties to play, all of them if not set
+    #[arg(long, value_enum, value_delimiter = ',')]
+    difficulty: Vec<Difficulty>,
+
+    /// Runs per difficulty, with seeds counting up from --first-seed
+    #[arg(long, value_name = "N", default_value_t = 10)]
+    seeds: u64,
+
+    #[arg(long, value_name = "SEED", default_value_t = 1)]
+    first_seed: u64,
+
+    /// Runs still going after this many ticks (60 per second) are stopped there
+    #[arg(long, value_name = "N", default_value_t = 36_000)]
+    max_ticks: u64,
+
+    /// Directory the report is written to
+    #[arg(long, value_name = "DIR", default_value = "balance")]
+    out: PathBuf,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let config = BalanceConfig {
+        difficulties: if args.difficulty.is_empty() {
+            Difficulty::ALL.to_vec()
+        } else {
+            args.difficulty
+        },
+        seeds: (args.first_seed..args.first_seed + args.seeds).collect(),
+        max_ticks: args.max_ticks,
+    };
+
+    let report = BalanceReport::play(&config, |run| {
+        eprintln!(
+            "{:?} seed {}: {:.0}s, {} kills, {} deaths by laser, {} by ram",
+            run.difficulty,
+            run.seed,
+            run.seconds,
+            run.kills,
+            run.deaths_by_laser,
+            run.deaths_by_ram
+        );
+    });
+
+    if let Err(err) = report.save(&args.out) {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
+    for summary in report.difficulties.iter() {
+        println!(
+            "{:?}: {:.0}s survived on average, {:.1} kills per minute, {} deaths by laser, {} by ram",
+            summary.difficulty,
+            summary.mean_seconds,
+            summary.kills_per_minute,
+            summary.deaths_by_laser,
+            summary.deaths_by_ram
+        );
+    }
+    println!("report written to {}", args.out.display());
+}
@@ -0,0 +1,263 @@
+use crate::audio::{PlaySound, Sound};
+use crate::collision::{first_contacts, CollisionEvent, CollisionLayer, CollisionSystem};
+use crate::components::{BunkerCell, ExplosionSize, ExplosionToSpawn, SpriteSize};
+use crate::menu::MenuSystem;
+use crate::settings::load_ron;
+use crate::{playing, NewGame, Wave, WinSize};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Shapes of the bunkers: '#' = cell
+const ARCH: &[&str] = &[
+    "   ######   ",
+    "  ########  ",
+    " ########## ",
+    "############",
+    "####    ####",
+    "###      ###",
+];
+
+const BLOCK: &[&str] = &["########", "########", "########", "##    ##"];
+
+/// Bunkers per wave and their shape, for when `BUNKERS_FILE` is missing or unreadable
+const BUNKER_WAVES: &[(usize, &[&str])] = &[(4, ARCH), (3, ARCH), (3, BLOCK), (2, BLOCK)];
+
+const CELL_SIZE: f32 = 6.;
+const CELL_HEALTH: u8 = 2;
+const BUNKER_ELEVATION: f32 = 120.;
+const BUNKER_COLOR: Color = Color::rgb(0.35, 0.85, 0.35);
+
+pub struct BunkerPlugin;
+
+impl Plugin for BunkerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BunkersBuilt>()
+            .init_resource::<BunkerLayouts>()
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                bunker_new_game_system.after(MenuSystem),
+            )
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(playing)
+                    .with_system(bunker_hit_system.after(CollisionSystem::Detect)),
+            )
+            // after the tick's gameplay, which may have started the next wave
+            .add_system_set_to_stage(
+                CoreStage::PostUpdate,
+                SystemSet::new()
+                    .with_run_criteria(playing)
+                    .with_system(bunker_wave_system),
+            );
+    }
+}
+
+/// Resource - bunker layout of each wave, loaded from `BUNKERS_FILE` by `run()`.
+/// Waves past the end of the list reuse the last layout.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct BunkerLayouts(pub Vec<BunkerWave>);
+
+/// Bunker layout of a wave: how many bunkers, and the rows of cells of each one ('#' = cell)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BunkerWave {
+    pub count: usize,
+    pub shape: Vec<String>,
+}
+
+impl Default for BunkerLayouts {
+    fn default() -> Self {
+        Self(
+            BUNKER_WAVES
+                .iter()
+                .map(|(count, shape)| BunkerWave {
+                    count: *count,
+                    shape: shape.iter().map(|row| row.to_string()).collect(),
+                })
+                .collect(),
+        )
+    }
+}
+
+impl BunkerLayouts {
+    /// `BUNKER_WAVES` if the file is missing, unreadable or lists no wave
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        if !path.exists() {
+            warn!("{} not found, using the default bunkers", path.display());
+        }
+        load_ron(path)
+            .filter(|layouts: &Self| !layouts.0.is_empty())
+            .unwrap_or_default()
+    }
+
+    pub fn wave(&self, wave: u32) -> &BunkerWave {
+        let index = (wave as usize).saturating_sub(1).min(self.0.len() - 1);
+        &self.0[index]
+    }
+}
+
+/// Resource - wave the bunkers on the field were built for (0 = none yet)
+#[derive(Default)]
+pub struct BunkersBuilt(pub u32);
+
+fn bunker_new_game_system(mut built: ResMut<BunkersBuilt>, mut events: EventReader<NewGame>) {
+    if events.iter().count() > 0 {
+        built.0 = 0;
+    }
+}
+
+/// Rebuilds the bunkers from `BunkerLayouts` whenever a new wave starts
+fn bunker_wave_system(
+    mut commands: Commands,
+    wave: Res<Wave>,
+    layouts: Res<BunkerLayouts>,
+    mut built: ResMut<BunkersBuilt>,
+    win_size: Res<WinSize>,
+    query: Query<Entity, With<BunkerCell>>,
+) {
+    if wave.0 == built.0 {
+        return;
+    }
+    built.0 = wave.0;
+
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+
+    let layout = layouts.wave(wave.0);
+
+    let rows = layout.shape.len() as f32;
+    let columns = layout
+        .shape
+        .iter()
+        .map(|row| row.chars().count())
+        .max()
+        .unwrap_or(0) as f32;
+    let y = -win_size.height / 2. + BUNKER_ELEVATION;
+
+    for i in 0..layout.count {
+        let x = -win_size.width / 2. + win_size.width * (i + 1) as f32 / (layout.count + 1) as f32;
+
+        for (row, line) in layout.shape.iter().enumerate() {
+            for (column, _) in line.chars().enumerate().filter(|(_, c)| *c == '#') {
+                let cell_x = x + (column as f32 - (columns - 1.) / 2.) * CELL_SIZE;
+                let cell_y = y + ((rows - 1.) / 2. - row as f32) * CELL_SIZE;
+
+                let translation = Vec3::new(cell_x, cell_y, 5.);
+                spawn_bunker_cell(&mut commands, translation, CELL_HEALTH);
+            }
+        }
+    }
+}
+
+pub fn spawn_bunker_cell(commands: &mut Commands, translation: Vec3, health: u8) -> Entity {
+    let mut color = BUNKER_COLOR;
+    color.set_a(health as f32 / CELL_HEALTH as f32);
+
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color,
+                custom_size: Some(Vec2::splat(CELL_SIZE)),
+                ..default()
+            },
+            transform: Transform::from_translation(translation),
+            ..default()
+        })
+        .insert(BunkerCell { health })
+        .insert(CollisionLayer::Shield)
+        .insert(SpriteSize::from((CELL_SIZE, CELL_SIZE)))
+        .id()
+}
+
+/// Lasers of both sides erode the cell they hit first
+fn bunker_hit_system(
+    mut commands: Commands,
+    mut events: EventReader<CollisionEvent>,
+    mut query: Query<(&mut BunkerCell, &mut Sprite)>,
+    lasers: Query<&Transform>,
+    mut sounds: EventWriter<PlaySound>,
+) {
+    for event in first_contacts(events.iter()) {
+        let hit = event
+            .between(CollisionLayer::Shield, CollisionLayer::PlayerLaser)
+            .or_else(|| event.between(CollisionLayer::Shield, CollisionLayer::EnemyLaser));
+
+        if let Some((cell_entity, laser_entity)) = hit {
+            if let Ok(transform) = lasers.get(laser_entity) {
+                commands.spawn().insert(ExplosionToSpawn {
+                    translation: transform.translation,
+                    size: ExplosionSize::Small,
+                });
+            }
+            commands.entity(laser_entity).despawn();
+            sounds.send(PlaySound(Sound::Hit));
+
+            if let Ok((mut cell, mut sprite)) = query.get_mut(cell_entity) {
+                cell.health = cell.health.saturating_sub(1);
+                if cell.health == 0 {
+                    commands.entity(cell_entity).despawn();
+                } else {
+                    sprite.color.set_a(cell.health as f32 / CELL_HEALTH as f32);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shipped_layouts_load() {
+        let path = crate::shipped_file(crate::BUNKERS_FILE);
+        let layouts: BunkerLayouts = load_ron(&path).unwrap();
+        assert!(!layouts.0.is_empty());
+        assert!(layouts.0.iter().all(|layout| layout.count > 0));
+    }
+
+    #[test]
+    fn missing_or_empty_files_fall_back_to_the_table() {
+        let missing = BunkerLayouts::load("no/such/bunkers.ron");
+        assert_eq!(missing, BunkerLayouts::default());
+        assert_eq!(missing.0.len(), BUNKER_WAVES.len());
+
+        let path = std::env::temp_dir().join("rust_invaders_empty_bunkers.ron");
+        std::fs::write(&path, "[]").unwrap();
+        assert_eq!(BunkerLayouts::load(&path), BunkerLayouts::default());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn later_waves_reuse_the_last_layout() {
+        let layouts: BunkerLayouts =
+            ron::from_str(r###"[(count: 4, shape: ["##"]), (count: 2, shape: ["#"])]"###).unwrap();
+        assert_eq!(layouts.wave(1).count, 4);
+        assert_eq!(layouts.wave(2).count, 2);
+        assert_eq!(layouts.wave(9).count, 2);
+    }
+
+    #[test]
+    fn shapes_are_centred_by_character() {
+        let mut app = crate::headless_app(crate::settings::Settings::default(), 1, 1);
+        app.insert_resource(BunkerLayouts(vec![BunkerWave {
+            count: 1,
+            shape: vec!["é#é".to_string()],
+        }]));
+        crate::start_run(&mut app);
+        app.update();
+        app.update();
+
+        let mut cells = app
+            .world
+            .query_filtered::<&Transform, With<crate::components::BunkerCell>>();
+        let xs: Vec<f32> = cells
+            .iter(&app.world)
+            .map(|transform| transform.translation.x)
+            .collect();
+        assert_eq!(xs, vec![0.]);
+    }
+}
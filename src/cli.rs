@@ -0,0 +1,81 @@
+use crate::difficulty::Difficulty;
+use clap::Parser;
+use std::path::PathBuf;
+
+/// Rust Invaders - shoot down the enemy formations before they get you
+///
+/// --headless, --autopilot, --replay and --record start a run right away, without the title screen.
+#[derive(Parser, Debug)]
+#[command(version, about)]
+pub struct Cli {
+    /// Window width, in pixels
+    #[arg(long, default_value_t = 598.)]
+    pub width: f32,
+
+    /// Window height, in pixels
+    #[arg(long, default_value_t = 676.)]
+    pub height: f32,
+
+    /// Borderless fullscreen window
+    #[arg(long, conflicts_with = "headless")]
+    pub fullscreen: bool,
+
+    /// Seed of every run, for reproducible sessions (random per run otherwise)
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Difficulty of new runs, instead of the one in the settings file
+    #[arg(long, value_enum)]
+    pub difficulty: Option<Difficulty>,
+
+    /// Ease off when the player keeps dying
+    #[arg(long)]
+    pub adaptive: bool,
+
+    /// Settings file to load and save
+    #[arg(long, value_name = "PATH", default_value = "settings.ron")]
+    pub config: PathBuf,
+
+    /// Wave new runs start at
+    #[arg(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    pub wave: u32,
+
+    /// Run the simulation without window, input or sound
+    #[arg(long, requires = "ticks")]
+    pub headless: bool,
+
+    /// Ticks (1/60 s of game time each) to simulate in headless mode, then print the result
+    #[arg(long, value_name = "N", requires = "headless")]
+    pub ticks: Option<u64>,
+
+    /// Let a bot play the runs (attract mode, soak tests)
+    #[arg(long, conflicts_with = "replay")]
+    pub autopilot: bool,
+
+    /// Play back a run recorded with --record (it brings its own seed, difficulty, wave and
+    /// window size)
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = [
+            "record", "seed", "difficulty", "adaptive", "wave", "width", "height", "fullscreen"
+        ]
+    )]
+    pub replay: Option<PathBuf>,
+
+    /// Record the inputs of the run, to replay it later
+    #[arg(long, value_name = "PATH")]
+    pub record: Option<PathBuf>,
+
+    /// Write the gameplay state of the world when the app exits, to compare runs
+    #[arg(long, value_name = "PATH")]
+    pub snapshot: Option<PathBuf>,
+
+    /// Write a checksum of the gameplay state for every tick of the run, to find where runs diverge
+    #[arg(long, value_name = "PATH")]
+    pub checksums: Option<PathBuf>,
+
+    /// Start with the sound muted
+    #[arg(long)]
+    pub mute: bool,
+}
@@ -0,0 +1,430 @@
+use crate::components::{PreviousTranslation, SpriteSize};
+use crate::COLLISION_CELL_SIZE;
+use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+
+/// Plugin - rebuilds the collision broadphase and emits `CollisionEvent`s every tick
+pub struct CollisionPlugin;
+
+impl Plugin for CollisionPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(CollisionGrid::default())
+            .add_event::<CollisionEvent>()
+            .add_system_to_stage(CoreStage::PreUpdate, previous_translation_system)
+            .add_system(collision_grid_system.label(CollisionSystem::Broadphase))
+            .add_system(
+                collision_detect_system
+                    .label(CollisionSystem::Detect)
+                    .after(CollisionSystem::Broadphase),
+            );
+    }
+}
+
+/// Label - movement systems run before `Broadphase`,
+/// collision event subscribers after `Detect`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, SystemLabel)]
+pub enum CollisionSystem {
+    Broadphase,
+    Detect,
+}
+
+/// Component - what an entity is, as far as collisions are concerned
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CollisionLayer {
+    Player,
+    Enemy,
+    PlayerLaser,
+    EnemyLaser,
+    Shield,
+}
+
+impl CollisionLayer {
+    /// Whether two layers generate events when they touch (order independent)
+    pub fn interacts(self, other: CollisionLayer) -> bool {
+        use CollisionLayer::*;
+        matches!(
+            (self.min(other), self.max(other)),
+            (Player, Enemy)
+                | (Player, EnemyLaser)
+                | (Enemy, PlayerLaser)
+                | (PlayerLaser, Shield)
+                | (EnemyLaser, Shield)
+        )
+    }
+}
+
+/// Event - two interacting colliders touched during this tick.
+/// `a` is always the entity on `layers.0`, and `layers.0 <= layers.1`.
+/// Events of a tick are sent in order of `time` (0 = start of tick, 1 = end of tick).
+#[derive(Debug, Clone, Copy)]
+pub struct CollisionEvent {
+    pub a: Entity,
+    pub b: Entity,
+    pub layers: (CollisionLayer, CollisionLayer),
+    pub time: f32,
+}
+
+impl CollisionEvent {
+    /// The two entities in the order of the given layers, if this event is between them
+    pub fn between(
+        &self,
+        first: CollisionLayer,
+        second: CollisionLayer,
+    ) -> Option<(Entity, Entity)> {
+        if self.layers == (first, second) {
+            Some((self.a, self.b))
+        } else if self.layers == (second, first) {
+            Some((self.b, self.a))
+        } else {
+            None
+        }
+    }
+}
+
+/// Keeps only the events whose entities did not already collide with something earlier
+/// in the same batch, so every subscriber agrees on which single contact each entity had.
+pub fn first_contacts<'a>(
+    events: impl Iterator<Item = &'a CollisionEvent>,
+) -> impl Iterator<Item = &'a CollisionEvent> {
+    let mut touched: HashSet<Entity> = HashSet::default();
+    events.filter(move |event| {
+        let first = !touched.contains(&event.a) && !touched.contains(&event.b);
+        if first {
+            touched.insert(event.a);
+            touched.insert(event.b);
+        }
+        first
+    })
+}
+
+/// Resource - uniform grid of every collider, keyed by cell.
+/// An entity is stored in every cell its (scaled) bounding box overlaps.
+pub struct CollisionGrid {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<Entity>>,
+}
+
+impl Default for CollisionGrid {
+    fn default() -> Self {
+        Self::new(COLLISION_CELL_SIZE)
+    }
+}
+
+impl CollisionGrid {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::default(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+    }
+
+    pub fn insert(&mut self, entity: Entity, center: Vec3, size: Vec2) {
+        let ((x_min, y_min), (x_max, y_max)) = self.cell_range(center, size);
+        for x in x_min..=x_max {
+            for y in y_min..=y_max {
+                self.cells.entry((x, y)).or_default().push(entity);
+            }
+        }
+    }
+
+    /// Entities sharing at least one cell with the given box, without duplicates.
+    /// Candidates still need a narrowphase test (e.g. `sweep_aabb`).
+    pub fn candidates(&self, center: Vec3, size: Vec2) -> Vec<Entity> {
+        let ((x_min, y_min), (x_max, y_max)) = self.cell_range(center, size);
+        let mut candidates = Vec::new();
+        for x in x_min..=x_max {
+            for y in y_min..=y_max {
+                if let Some(cell) = self.cells.get(&(x, y)) {
+                    candidates.extend_from_slice(cell);
+                }
+            }
+        }
+        candidates.sort_unstable();
+        candidates.dedup();
+        candidates
+    }
+
+    fn cell_range(&self, center: Vec3, size: Vec2) -> ((i32, i32), (i32, i32)) {
+        let min = (center.truncate() - size / 2.) / self.cell_size;
+        let max = (center.truncate() + size / 2.) / self.cell_size;
+        (
+            (min.x.floor() as i32, min.y.floor() as i32),
+            (max.x.floor() as i32, max.y.floor() as i32),
+        )
+    }
+}
+
+/// Size of the axis aligned box a collider covers: its `SpriteSize`, scaled and rotated
+/// like its sprite (e.g. enemy lasers, flipped upside down)
+pub fn collider_size(transform: &Transform, size: &SpriteSize) -> Vec2 {
+    let half = (size.0 * transform.scale.truncate()).abs() / 2.;
+    let rotation = Mat3::from_quat(transform.rotation);
+    let extent =
+        |axis: usize| rotation.x_axis[axis].abs() * half.x + rotation.y_axis[axis].abs() * half.y;
+    Vec2::new(extent(0), extent(1)) * 2.
+}
+
+/// Swept AABB test of two boxes moving linearly from `*_from` to `*_to` over one tick.
+/// Returns the fraction of the tick at which they first overlap, so fast movers
+/// can't tunnel through each other between two frames. Touching edges don't count.
+pub fn sweep_aabb(
+    a_from: Vec2,
+    a_to: Vec2,
+    a_size: Vec2,
+    b_from: Vec2,
+    b_to: Vec2,
+    b_size: Vec2,
+) -> Option<f32> {
+    // move in b's frame of reference: a point against a box of both half sizes
+    let half = (a_size + b_size) / 2.;
+    let start = a_from - b_from;
+    let motion = (a_to - a_from) - (b_to - b_from);
+
+    let mut enter: f32 = 0.;
+    let mut exit: f32 = 1.;
+    for (start, motion, half) in [(start.x, motion.x, half.x), (start.y, motion.y, half.y)] {
+        if motion == 0. {
+            if start <= -half || start >= half {
+                return None;
+            }
+        } else {
+            let t0 = (-half - start) / motion;
+            let t1 = (half - start) / motion;
+            enter = enter.max(t0.min(t1));
+            exit = exit.min(t0.max(t1));
+            if enter >= exit {
+                return None;
+            }
+        }
+    }
+
+    Some(enter)
+}
+
+/// Box covered by a collider during the last tick
+#[derive(Clone, Copy)]
+struct Sweep {
+    from: Vec2,
+    to: Vec2,
+    size: Vec2,
+}
+
+impl Sweep {
+    fn new(
+        transform: &Transform,
+        size: &SpriteSize,
+        previous: Option<&PreviousTranslation>,
+    ) -> Self {
+        let to = transform.translation.truncate();
+        Self {
+            from: previous.map_or(to, |previous| previous.0.truncate()),
+            to,
+            size: collider_size(transform, size),
+        }
+    }
+
+    /// Center and size of the box enclosing the whole sweep, for the broadphase
+    fn bounds(&self) -> (Vec3, Vec2) {
+        let center = (self.from + self.to) / 2.;
+        (center.extend(0.), self.size + (self.to - self.from).abs())
+    }
+
+    fn hit(&self, other: &Sweep) -> Option<f32> {
+        sweep_aabb(
+            self.from, self.to, self.size, other.from, other.to, other.size,
+        )
+    }
+}
+
+fn previous_translation_system(mut query: Query<(&Transform, &mut PreviousTranslation)>) {
+    for (transform, mut previous) in query.iter_mut() {
+        previous.0 = transform.translation;
+    }
+}
+
+pub fn collision_grid_system(
+    mut grid: ResMut<CollisionGrid>,
+    query: Query<
+        (
+            Entity,
+            &Transform,
+            &SpriteSize,
+            Option<&PreviousTranslation>,
+        ),
+        With<CollisionLayer>,
+    >,
+) {
+    grid.clear();
+    for (entity, transform, size, previous) in query.iter() {
+        let (center, size) = Sweep::new(transform, size, previous).bounds();
+        grid.insert(entity, center, size);
+    }
+}
+
+pub fn collision_detect_system(
+    grid: Res<CollisionGrid>,
+    mut events: EventWriter<CollisionEvent>,
+    query: Query<(
+        Entity,
+        &Transform,
+        &SpriteSize,
+        Option<&PreviousTranslation>,
+        &CollisionLayer,
+    )>,
+) {
+    let mut collisions = Vec::new();
+
+    for (entity, transform, size, previous, layer) in query.iter() {
+        let sweep = Sweep::new(transform, size, previous);
+        let (center, bounds) = sweep.bounds();
+
+        for other in grid.candidates(center, bounds) {
+            // each pair is visited from both sides, keep only one
+            if other <= entity {
+                continue;
+            }
+
+            let (other_sweep, other_layer) = match query.get(other) {
+                Ok((_, transform, size, previous, layer)) => {
+                    (Sweep::new(transform, size, previous), layer)
+                }
+                Err(_) => continue,
+            };
+
+            if !layer.interacts(*other_layer) {
+                continue;
+            }
+
+            if let Some(time) = sweep.hit(&other_sweep) {
+                let (a, b, layers) = if layer <= other_layer {
+                    (entity, other, (*layer, *other_layer))
+                } else {
+                    (other, entity, (*other_layer, *layer))
+                };
+                collisions.push(CollisionEvent { a, b, layers, time });
+            }
+        }
+    }
+
+    // earliest contacts first, so `first_contacts` keeps what really happened first
+    collisions.sort_by(|a, b| a.time.total_cmp(&b.time));
+    events.send_batch(collisions.into_iter());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+    fn at(x: f32, y: f32) -> Transform {
+        Transform::from_xyz(x, y, 0.)
+    }
+
+    /// Whether two colliders standing still overlap, as `collision_detect_system` sees them
+    fn hit(a: &Transform, a_size: &SpriteSize, b: &Transform, b_size: &SpriteSize) -> bool {
+        let sweep = |transform, size| Sweep::new(transform, size, None);
+        sweep(a, a_size).hit(&sweep(b, b_size)).is_some()
+    }
+
+    fn assert_near(size: Vec2, expected: Vec2) {
+        assert!(
+            (size - expected).abs().max_element() < 1e-4,
+            "{} is not {}",
+            size,
+            expected
+        );
+    }
+
+    #[test]
+    fn colliders_scale_with_their_own_transform() {
+        let enemy = SpriteSize::from((84., 93.));
+        let laser = SpriteSize::from((17., 55.));
+        let enemy_at = at(0., 0.).with_scale(Vec3::new(0.5, 0.5, 1.));
+        assert_near(collider_size(&enemy_at, &enemy), Vec2::new(42., 46.5));
+
+        // reach of the enemy is 21 sideways, whatever the size of the laser
+        for laser_scale in [0.25, 0.5, 1.] {
+            let scale = Vec3::new(laser_scale, laser_scale, 1.);
+            let half_laser = 17. * laser_scale / 2.;
+            let touching = at(21. + half_laser - 0.1, 0.).with_scale(scale);
+            let apart = at(21. + half_laser + 0.1, 0.).with_scale(scale);
+            assert!(hit(&enemy_at, &enemy, &touching, &laser));
+            assert!(!hit(&enemy_at, &enemy, &apart, &laser));
+        }
+
+        // mirrored sprites cover the same box
+        let mirrored = at(0., 0.).with_scale(Vec3::new(-0.5, 0.5, 1.));
+        assert_near(collider_size(&mirrored, &enemy), Vec2::new(42., 46.5));
+    }
+
+    #[test]
+    fn colliders_rotate_with_their_sprite() {
+        let laser = SpriteSize::from((10., 40.));
+
+        // enemy lasers are flipped upside down, which keeps their box
+        let flipped = at(0., 0.).with_rotation(Quat::from_rotation_x(PI));
+        assert_near(collider_size(&flipped, &laser), Vec2::new(10., 40.));
+
+        let quarter = at(0., 0.).with_rotation(Quat::from_rotation_z(FRAC_PI_2));
+        assert_near(collider_size(&quarter, &laser), Vec2::new(40., 10.));
+
+        // a tilted box is covered by a larger axis aligned one
+        let tilted = at(0., 0.).with_rotation(Quat::from_rotation_z(FRAC_PI_4));
+        let side = 50. * FRAC_PI_4.cos();
+        assert_near(collider_size(&tilted, &laser), Vec2::splat(side));
+
+        let player = SpriteSize::from((20., 20.));
+        assert!(hit(&quarter, &laser, &at(29., 0.), &player));
+        assert!(!hit(&at(0., 0.), &laser, &at(29., 0.), &player));
+    }
+
+    #[test]
+    fn touching_edges_do_not_collide() {
+        let size = SpriteSize::from((10., 10.));
+        assert!(!hit(&at(0., 0.), &size, &at(10., 0.), &size));
+        assert!(!hit(&at(0., 0.), &size, &at(10., 10.), &size));
+        assert!(hit(&at(0., 0.), &size, &at(9.9, 9.9), &size));
+
+        let size = Vec2::splat(10.);
+        // sliding along an edge
+        assert_eq!(
+            sweep_aabb(
+                Vec2::new(0., 10.),
+                Vec2::new(50., 10.),
+                size,
+                Vec2::ZERO,
+                Vec2::ZERO,
+                size
+            ),
+            None
+        );
+        // stopping right at the edge
+        assert_eq!(
+            sweep_aabb(
+                Vec2::new(-30., 0.),
+                Vec2::new(-10., 0.),
+                size,
+                Vec2::ZERO,
+                Vec2::ZERO,
+                size
+            ),
+            None
+        );
+        // through it within the tick, entering halfway
+        assert_eq!(
+            sweep_aabb(
+                Vec2::new(-30., 0.),
+                Vec2::new(10., 0.),
+                size,
+                Vec2::ZERO,
+                Vec2::ZERO,
+                size
+            ),
+            Some(0.5)
+        );
+    }
+}
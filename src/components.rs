@@ -1,4 +1,3 @@
-use bevy::core::Timer;
 use bevy::prelude::{Component, Vec2, Vec3};
 
 //region --Common Components
@@ -15,9 +14,17 @@ pub struct Movable {
 #[derive(Component)]
 pub struct Laser;
 
+/// Translation at the start of the current tick (for swept collisions)
+#[derive(Component)]
+pub struct PreviousTranslation(pub Vec3);
+
 #[derive(Component)]
 pub struct SpriteSize(pub Vec2);
 
+/// The camera of the field, the one screen shakes move
+#[derive(Component)]
+pub struct MainCamera;
+
 impl From<(f32, f32)> for SpriteSize {
     fn from(val: (f32, f32)) -> Self {
         SpriteSize(Vec2::new(val.0, val.1))
@@ -45,15 +52,32 @@ pub struct FromEnemy;
 #[derive(Component)]
 pub struct Explosion;
 
-#[derive(Component)]
-pub struct ExplosionToSpawn(pub Vec3);
+/// How big an explosion is, each size looks as set in `EffectsConfig`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExplosionSize {
+    Small,
+    Medium,
+    Big,
+}
 
 #[derive(Component)]
-pub struct ExplosionTimer(pub Timer);
+pub struct ExplosionToSpawn {
+    pub translation: Vec3,
+    pub size: ExplosionSize,
+}
 
-impl Default for ExplosionTimer {
-    fn default() -> Self {
-        Self(Timer::from_seconds(0.05, true))
-    }
+/// Debris of an explosion, fading out as it flies
+#[derive(Component)]
+pub struct Particle {
+    pub velocity: Vec2,
+    pub age: f32,
+    pub lifetime: f32,
 }
 //endregion --Explosion Components
+
+//region --Bunker Components
+#[derive(Component)]
+pub struct BunkerCell {
+    pub health: u8,
+}
+//endregion --Bunker Components
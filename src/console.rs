@@ -0,0 +1,551 @@
+use crate::components::Enemy;
+use crate::damage::Damage;
+use crate::difficulty::Tuning;
+use crate::enemy::spawn_custom_formation;
+use crate::menu::MenuSystem;
+use crate::settings::{KeyBindings, Settings};
+use crate::time::TimeScale;
+use crate::{EnemyCount, GameRng, GodMode, RunConfig, TickSystem, Wave, WaveProgress, WinSize};
+use bevy::ecs::event::Events;
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use bevy::window::ReceivedCharacter;
+
+const FONT: &str = "fonts/FiraMono-Medium.ttf";
+const TEXT_SIZE: f32 = 16.;
+const TEXT_COLOR: Color = Color::rgb(0.9, 0.9, 0.9);
+const BACKGROUND: Color = Color::rgba(0., 0., 0., 0.75);
+/// Lines of output kept on screen
+const OUTPUT_LINES: usize = 12;
+const HISTORY_MAX: usize = 100;
+/// Margin off the side of the field formations spawned from the console enter from
+const ENTRY_MARGIN: f32 = 100.;
+
+const HELP: &str = "commands: spawn enemy <x> <y> | spawn formation <x,y> <rx[,ry]> [members] | \
+god | kill all | wave <n> | set <key> <value> | timescale <f> | freeze | step [ticks] | seed [n] | help";
+
+/// Plugin - developer console, toggled with the console key. Takes over the keyboard while open.
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Console>()
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                console_input_system
+                    .after(InputSystem)
+                    .before(MenuSystem)
+                    .before(TickSystem::Intent),
+            )
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                console_execute_system.exclusive_system().at_start(),
+            )
+            .add_system(console_render_system);
+    }
+}
+
+/// Resource - what was typed, run and answered
+#[derive(Default)]
+struct Console {
+    open: bool,
+    input: String,
+    output: Vec<String>,
+    history: Vec<String>,
+    /// entry of `history` being browsed, from the most recent
+    browsing: Option<usize>,
+    /// line to run at the start of the next frame, with the whole world at hand
+    pending: Option<String>,
+}
+
+impl Console {
+    fn print(&mut self, line: String) {
+        self.output.push(line);
+        let extra = self.output.len().saturating_sub(OUTPUT_LINES);
+        self.output.drain(..extra);
+    }
+
+    fn browse(&mut self, older: bool) {
+        if self.history.is_empty() {
+            return;
+        }
+        let newest = self.history.len() - 1;
+        self.browsing = match (self.browsing, older) {
+            (None, true) => Some(0),
+            (Some(back), true) => Some((back + 1).min(newest)),
+            (Some(0), false) | (None, false) => None,
+            (Some(back), false) => Some(back - 1),
+        };
+        self.input = match self.browsing {
+            Some(back) => self.history[newest - back].clone(),
+            None => String::new(),
+        };
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConsoleCommand {
+    SpawnEnemy(Vec2),
+    SpawnFormation {
+        pivot: Vec2,
+        radius: Vec2,
+        members: Option<u32>,
+    },
+    God,
+    KillAll,
+    Wave(u32),
+    Set {
+        key: String,
+        value: String,
+    },
+    TimeScale(f32),
+    Freeze,
+    Step(u32),
+    Seed(Option<u64>),
+    Help,
+}
+
+impl ConsoleCommand {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let command = match words.as_slice() {
+            ["spawn", "enemy", x, y] => {
+                ConsoleCommand::SpawnEnemy(Vec2::new(number(x)?, number(y)?))
+            }
+            ["spawn", "enemy", ..] => return Err("usage: spawn enemy <x> <y>".to_string()),
+            ["spawn", "formation", pivot, radius, rest @ ..] if rest.len() <= 1 => {
+                ConsoleCommand::SpawnFormation {
+                    pivot: vector(pivot, false)?,
+                    radius: vector(radius, true)?,
+                    members: rest.first().map(|members| number(members)).transpose()?,
+                }
+            }
+            ["spawn", "formation", ..] => {
+                return Err("usage: spawn formation <x,y> <rx[,ry]> [members]".to_string())
+            }
+            ["spawn", ..] => return Err("spawn what? enemy or formation".to_string()),
+            ["god"] => ConsoleCommand::God,
+            ["kill", "all"] => ConsoleCommand::KillAll,
+            ["wave", n] => match number(n)? {
+                0 => return Err("waves start at 1".to_string()),
+                n => ConsoleCommand::Wave(n),
+            },
+            ["wave", ..] => return Err("usage: wave <n>".to_string()),
+            ["set", key, value @ ..] if !value.is_empty() => ConsoleCommand::Set {
+                key: key.to_string(),
+                value: value.join(" "),
+            },
+            ["set", ..] => return Err("usage: set <key> <value>".to_string()),
+            ["timescale", scale] => {
+                let scale: f32 = number(scale)?;
+                if !(TimeScale::MIN..=TimeScale::MAX).contains(&scale) {
+                    return Err(format!(
+                        "time scale goes from {} to {}",
+                        TimeScale::MIN,
+                        TimeScale::MAX
+                    ));
+                }
+                ConsoleCommand::TimeScale(scale)
+            }
+            ["timescale", ..] => return Err("usage: timescale <f>".to_string()),
+            ["freeze"] => ConsoleCommand::Freeze,
+            ["step"] => ConsoleCommand::Step(1),
+            ["step", ticks] => ConsoleCommand::Step(number(ticks)?),
+            ["step", ..] => return Err("usage: step [ticks]".to_string()),
+            ["seed"] => ConsoleCommand::Seed(None),
+            ["seed", seed] => ConsoleCommand::Seed(Some(number(seed)?)),
+            ["help"] => ConsoleCommand::Help,
+            [] => return Err(String::new()),
+            [word, ..] => return Err(format!("unknown command {}, try help", word)),
+        };
+        Ok(command)
+    }
+
+    /// Runs the command on the world, returns what to answer
+    pub fn run(self, world: &mut World) -> Result<String, String> {
+        match self {
+            ConsoleCommand::SpawnEnemy(position) => {
+                spawn_custom_formation(world, position, position, Vec2::ZERO, 1);
+                Ok(format!("enemy spawned at {}, {}", position.x, position.y))
+            }
+            ConsoleCommand::SpawnFormation {
+                pivot,
+                radius,
+                members,
+            } => {
+                let members =
+                    members.unwrap_or_else(|| world.resource::<Tuning>().formation_members_max());
+                // from the nearest side, at the height of the pivot
+                let side = world.resource::<WinSize>().width / 2. + ENTRY_MARGIN;
+                let start = Vec2::new(side.copysign(pivot.x), pivot.y);
+                spawn_custom_formation(world, start, pivot, radius, members);
+                Ok(format!("formation of {} spawned", members))
+            }
+            ConsoleCommand::God => {
+                let mut god_mode = world.resource_mut::<GodMode>();
+                god_mode.0 = !god_mode.0;
+                Ok(format!(
+                    "god mode {}",
+                    if god_mode.0 { "on" } else { "off" }
+                ))
+            }
+            ConsoleCommand::KillAll => {
+                let enemies: Vec<Entity> = world
+                    .query_filtered::<Entity, With<Enemy>>()
+                    .iter(world)
+                    .collect();
+                // dying like any other, on the next tick
+                let mut damages = world.resource_mut::<Events<Damage>>();
+                for enemy in enemies.iter() {
+                    damages.send(Damage::lethal(*enemy));
+                }
+                Ok(format!("{} enemies killed", enemies.len()))
+            }
+            ConsoleCommand::Wave(wave) => {
+                world.insert_resource(Wave(wave));
+                world.insert_resource(WaveProgress(0));
+                Ok(format!("wave {}", wave))
+            }
+            ConsoleCommand::Set { key, value } => {
+                let mut settings = Settings::from_world(world);
+                settings.set(&key, &value)?;
+                settings.apply(world);
+                Ok(format!("{} = {}", key, value))
+            }
+            ConsoleCommand::TimeScale(scale) => {
+                world.resource_mut::<TimeScale>().scale = scale;
+                Ok(format!("time scale {}", scale))
+            }
+            ConsoleCommand::Freeze => {
+                let mut time_scale = world.resource_mut::<TimeScale>();
+                time_scale.frozen = !time_scale.frozen;
+                Ok(format!(
+                    "game time {}",
+                    if time_scale.frozen {
+                        "frozen"
+                    } else {
+                        "running"
+                    }
+                ))
+            }
+            ConsoleCommand::Step(ticks) => {
+                world.resource_mut::<TimeScale>().step(ticks);
+                Ok(format!("stepping {} ticks", ticks))
+            }
+            ConsoleCommand::Seed(None) => Ok(format!("seed {}", world.resource::<GameRng>().seed)),
+            ConsoleCommand::Seed(Some(seed)) => {
+                world.resource_mut::<RunConfig>().seed = Some(seed);
+                Ok(format!("next runs use seed {}", seed))
+            }
+            ConsoleCommand::Help => Ok(HELP.to_string()),
+        }
+    }
+}
+
+fn number<T: std::str::FromStr>(word: &str) -> Result<T, String> {
+    word.parse()
+        .map_err(|_| format!("{} is not a valid number", word))
+}
+
+/// `x,y`, or a single number for both when `square` is allowed
+fn vector(word: &str, square: bool) -> Result<Vec2, String> {
+    match word.split_once(',') {
+        Some((x, y)) => Ok(Vec2::new(number(x)?, number(y)?)),
+        None if square => Ok(Vec2::splat(number(word)?)),
+        None => Err(format!("{} is not a x,y position", word)),
+    }
+}
+
+/// Typing, history and the toggle key. Whatever is pressed while open does not reach the game.
+fn console_input_system(
+    mut console: ResMut<Console>,
+    mut keyboard: ResMut<Input<KeyCode>>,
+    keys: Res<KeyBindings>,
+    mut characters: EventReader<ReceivedCharacter>,
+) {
+    let toggled = keyboard.just_pressed(keys.console);
+    if toggled {
+        console.open = !console.open;
+    }
+    if !console.open {
+        // typed for the game, not for the console
+        characters.iter().for_each(drop);
+        return;
+    }
+
+    for character in characters.iter() {
+        // the toggle key types a character too, when it opened the console
+        if !toggled && !character.char.is_control() {
+            console.input.push(character.char);
+        }
+    }
+
+    if keyboard.just_pressed(KeyCode::Back) {
+        console.input.pop();
+    }
+    if keyboard.just_pressed(KeyCode::Up) {
+        console.browse(true);
+    }
+    if keyboard.just_pressed(KeyCode::Down) {
+        console.browse(false);
+    }
+    if keyboard.just_pressed(KeyCode::Return) {
+        let line = std::mem::take(&mut console.input);
+        if !line.trim().is_empty() {
+            console.history.push(line.clone());
+            let extra = console.history.len().saturating_sub(HISTORY_MAX);
+            console.history.drain(..extra);
+        }
+        console.browsing = None;
+        console.pending = Some(line);
+    }
+
+    let pressed: Vec<KeyCode> = keyboard.get_pressed().copied().collect();
+    for key in pressed {
+        keyboard.reset(key);
+    }
+}
+
+fn console_execute_system(world: &mut World) {
+    let line = match world.resource_mut::<Console>().pending.take() {
+        Some(line) => line,
+        None => return,
+    };
+
+    let answer = ConsoleCommand::parse(&line).and_then(|command| command.run(world));
+    let mut console = world.resource_mut::<Console>();
+    console.print(format!("> {}", line));
+    match answer {
+        Ok(answer) => console.print(answer),
+        Err(err) if err.is_empty() => {}
+        Err(err) => console.print(format!("error: {}", err)),
+    }
+}
+
+#[derive(Component)]
+struct ConsoleRoot;
+
+#[derive(Component)]
+struct ConsoleText;
+
+fn console_render_system(
+    mut commands: Commands,
+    console: Res<Console>,
+    asset_server: Option<Res<AssetServer>>,
+    roots: Query<Entity, With<ConsoleRoot>>,
+    mut texts: Query<&mut Text, With<ConsoleText>>,
+) {
+    if !console.is_changed() {
+        return;
+    }
+    if !console.open {
+        for entity in roots.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    }
+
+    let mut value = console.output.join("\n");
+    if !value.is_empty() {
+        value.push('\n');
+    }
+    value.push_str(&format!("> {}_", console.input));
+
+    if let Ok(mut text) = texts.get_single_mut() {
+        text.sections[0].value = value;
+        return;
+    }
+
+    // nothing to show text with in headless runs
+    let asset_server = match asset_server {
+        Some(asset_server) => asset_server,
+        None => return,
+    };
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(0.),
+                    right: Val::Px(0.),
+                    bottom: Val::Px(0.),
+                    ..default()
+                },
+                padding: Rect::all(Val::Px(6.)),
+                ..default()
+            },
+            color: BACKGROUND.into(),
+            ..default()
+        })
+        .insert(ConsoleRoot)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        value,
+                        TextStyle {
+                            font: asset_server.load(FONT),
+                            font_size: TEXT_SIZE,
+                            color: TEXT_COLOR,
+                        },
+                        default(),
+                    ),
+                    ..default()
+                })
+                .insert(ConsoleText);
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{headless_app, start_run, GameClock};
+
+    fn app() -> App {
+        let mut app = headless_app(Settings::default(), 1, 1);
+        start_run(&mut app);
+        app.update();
+        app
+    }
+
+    fn run(app: &mut App, line: &str) -> Result<String, String> {
+        ConsoleCommand::parse(line).and_then(|command| command.run(&mut app.world))
+    }
+
+    #[test]
+    fn parses_commands_and_reports_errors() {
+        assert_eq!(
+            ConsoleCommand::parse("spawn enemy 10 -20.5"),
+            Ok(ConsoleCommand::SpawnEnemy(Vec2::new(10., -20.5)))
+        );
+        assert_eq!(
+            ConsoleCommand::parse("  spawn formation 0,100 120  3"),
+            Ok(ConsoleCommand::SpawnFormation {
+                pivot: Vec2::new(0., 100.),
+                radius: Vec2::splat(120.),
+                members: Some(3)
+            })
+        );
+        assert_eq!(
+            ConsoleCommand::parse("set difficulty Hard"),
+            Ok(ConsoleCommand::Set {
+                key: "difficulty".to_string(),
+                value: "Hard".to_string()
+            })
+        );
+        assert_eq!(
+            ConsoleCommand::parse("seed"),
+            Ok(ConsoleCommand::Seed(None))
+        );
+
+        assert!(ConsoleCommand::parse("spawn enemy 10").is_err());
+        assert!(ConsoleCommand::parse("spawn formation 10 20").is_err());
+        assert!(ConsoleCommand::parse("wave 0").is_err());
+        assert!(ConsoleCommand::parse("timescale 10").is_err());
+        assert_eq!(
+            ConsoleCommand::parse("wave x"),
+            Err("x is not a valid number".to_string())
+        );
+        assert_eq!(
+            ConsoleCommand::parse("fly"),
+            Err("unknown command fly, try help".to_string())
+        );
+    }
+
+    #[test]
+    fn commands_change_the_run() {
+        let mut app = app();
+        let enemies = app.world.resource::<EnemyCount>().0;
+
+        run(&mut app, "spawn enemy 50 60").unwrap();
+        run(&mut app, "spawn formation -100,50 80,60 3").unwrap();
+        app.update();
+        assert_eq!(app.world.resource::<EnemyCount>().0, enemies + 4);
+
+        // the lone enemy holds still where it appeared
+        for _ in 0..30 {
+            app.update();
+        }
+        let held = app
+            .world
+            .query_filtered::<&Transform, With<Enemy>>()
+            .iter(&app.world)
+            .any(|transform| transform.translation.truncate() == Vec2::new(50., 60.));
+        assert!(held);
+
+        assert_eq!(
+            run(&mut app, "kill all"),
+            Ok(format!("{} enemies killed", enemies + 4))
+        );
+        app.update();
+        assert_eq!(app.world.resource::<EnemyCount>().0, 0);
+
+        run(&mut app, "wave 4").unwrap();
+        assert_eq!(app.world.resource::<Wave>().0, 4);
+
+        assert_eq!(run(&mut app, "god"), Ok("god mode on".to_string()));
+        assert!(app.world.resource::<GodMode>().0);
+
+        run(&mut app, "set audio.volume 0.25").unwrap();
+        assert_eq!(
+            app.world.resource::<crate::audio::AudioSettings>().volume,
+            0.25
+        );
+        assert!(run(&mut app, "set audio.loudness 1").is_err());
+        assert!(run(&mut app, "set difficulty Impossible").is_err());
+
+        assert_eq!(run(&mut app, "seed"), Ok("seed 1".to_string()));
+    }
+
+    #[test]
+    fn frozen_time_moves_only_by_steps() {
+        let mut app = app();
+        let tick = |app: &App| app.world.resource::<GameClock>().tick;
+
+        assert_eq!(run(&mut app, "freeze"), Ok("game time frozen".to_string()));
+        let frozen_at = tick(&app);
+        for _ in 0..10 {
+            app.update();
+        }
+        assert_eq!(tick(&app), frozen_at);
+
+        run(&mut app, "step 3").unwrap();
+        for _ in 0..10 {
+            app.update();
+        }
+        assert_eq!(tick(&app), frozen_at + 3);
+
+        run(&mut app, "freeze").unwrap();
+        app.update();
+        assert_eq!(tick(&app), frozen_at + 4);
+
+        let mut time_scale = TimeScale::default();
+        time_scale.slower();
+        assert_eq!(time_scale.scale, 0.5);
+        for _ in 0..5 {
+            time_scale.slower();
+        }
+        assert_eq!(time_scale.scale, TimeScale::MIN);
+        time_scale.scale = 3.;
+        time_scale.faster();
+        time_scale.faster();
+        assert_eq!(time_scale.scale, TimeScale::MAX);
+    }
+
+    #[test]
+    fn history_browses_back_and_forth() {
+        let mut console = Console {
+            history: vec!["god".to_string(), "wave 2".to_string()],
+            ..default()
+        };
+        console.browse(true);
+        assert_eq!(console.input, "wave 2");
+        console.browse(true);
+        console.browse(true);
+        assert_eq!(console.input, "god");
+        console.browse(false);
+        assert_eq!(console.input, "wave 2");
+        console.browse(false);
+        assert_eq!(console.input, "");
+    }
+}
@@ -0,0 +1,193 @@
+use crate::collision::{CollisionLayer, CollisionSystem};
+use crate::playing;
+use bevy::prelude::*;
+
+/// Plugin - applies `Damage`: an entity whose `Health` runs out dies exactly once,
+/// with a `Death` event, however many hits it took that tick
+pub struct DamagePlugin;
+
+impl Plugin for DamagePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Damage>()
+            .add_event::<Death>()
+            .add_system_set(
+                SystemSet::new().with_run_criteria(playing).with_system(
+                    damage_system
+                        .label(DamageSystem)
+                        .after(CollisionSystem::Detect),
+                ),
+            );
+    }
+}
+
+/// Label - systems sending `Damage` run before, `Death` subscribers after
+#[derive(Debug, Clone, PartialEq, Eq, Hash, SystemLabel)]
+pub struct DamageSystem;
+
+/// Component - hits an entity takes before dying. Dead at 0, until its despawn applies.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Health(pub u32);
+
+/// Event - `amount` of health taken from `target`, by `source` when something hit it
+/// (e.g. the laser)
+#[derive(Debug, Clone, Copy)]
+pub struct Damage {
+    pub target: Entity,
+    pub amount: u32,
+    pub source: Option<Entity>,
+}
+
+impl Damage {
+    pub fn hit(target: Entity, source: Entity) -> Self {
+        Self {
+            target,
+            amount: 1,
+            source: Some(source),
+        }
+    }
+
+    /// Whatever the health left (developer console)
+    pub fn lethal(target: Entity) -> Self {
+        Self {
+            target,
+            amount: u32::MAX,
+            source: None,
+        }
+    }
+}
+
+/// Event - an entity ran out of health, sent once the tick it is despawned.
+/// `source` is what dealt the last hit, and its layer.
+#[derive(Debug, Clone, Copy)]
+pub struct Death {
+    pub entity: Entity,
+    pub layer: CollisionLayer,
+    pub translation: Vec3,
+    pub source: Option<(Entity, CollisionLayer)>,
+}
+
+impl Death {
+    /// Whether this is an enemy shot down by the player
+    pub fn is_kill(&self) -> bool {
+        self.layer == CollisionLayer::Enemy
+            && matches!(self.source, Some((_, CollisionLayer::PlayerLaser)))
+    }
+}
+
+fn damage_system(
+    mut commands: Commands,
+    mut damages: EventReader<Damage>,
+    mut deaths: EventWriter<Death>,
+    mut targets: Query<(&mut Health, &Transform, &CollisionLayer)>,
+    sources: Query<&CollisionLayer>,
+) {
+    for damage in damages.iter() {
+        let (mut health, transform, layer) = match targets.get_mut(damage.target) {
+            Ok(target) => target,
+            Err(_) => continue,
+        };
+        // already dead, despawned at the end of the stage
+        if health.0 == 0 {
+            continue;
+        }
+
+        health.0 = health.0.saturating_sub(damage.amount);
+        if health.0 == 0 {
+            commands.entity(damage.target).despawn();
+            deaths.send(Death {
+                entity: damage.target,
+                layer: *layer,
+                translation: transform.translation,
+                source: damage
+                    .source
+                    .and_then(|source| Some((source, *sources.get(source).ok()?))),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::event::Events;
+
+    /// Every death so far
+    #[derive(Default)]
+    struct Deaths(Vec<Death>);
+
+    fn record_system(mut events: EventReader<Death>, mut deaths: ResMut<Deaths>) {
+        deaths.0.extend(events.iter().copied());
+    }
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_event::<Damage>()
+            .add_event::<Death>()
+            .init_resource::<Deaths>()
+            .add_system(damage_system.label(DamageSystem))
+            .add_system(record_system.after(DamageSystem));
+        app
+    }
+
+    fn spawn(app: &mut App, layer: CollisionLayer, health: u32) -> Entity {
+        app.world
+            .spawn()
+            .insert(layer)
+            .insert(Health(health))
+            .insert(Transform::default())
+            .id()
+    }
+
+    fn damage(app: &mut App, damage: Damage) {
+        app.world.resource_mut::<Events<Damage>>().send(damage);
+    }
+
+    #[test]
+    fn hits_in_the_same_tick_kill_once() {
+        let mut app = app();
+        let enemy = spawn(&mut app, CollisionLayer::Enemy, 1);
+        let lasers = [
+            spawn(&mut app, CollisionLayer::PlayerLaser, 1),
+            spawn(&mut app, CollisionLayer::PlayerLaser, 1),
+        ];
+
+        for laser in lasers {
+            damage(&mut app, Damage::hit(enemy, laser));
+        }
+        app.update();
+        assert!(app.world.get_entity(enemy).is_none());
+
+        // hits on what is already gone are ignored
+        damage(&mut app, Damage::hit(enemy, lasers[1]));
+        app.update();
+
+        let deaths = &app.world.resource::<Deaths>().0;
+        assert_eq!(deaths.len(), 1);
+        assert_eq!(deaths[0].entity, enemy);
+        assert_eq!(deaths[0].layer, CollisionLayer::Enemy);
+        assert_eq!(
+            deaths[0].source,
+            Some((lasers[0], CollisionLayer::PlayerLaser))
+        );
+    }
+
+    #[test]
+    fn health_takes_several_hits() {
+        let mut app = app();
+        let enemy = spawn(&mut app, CollisionLayer::Enemy, 3);
+        let laser = spawn(&mut app, CollisionLayer::PlayerLaser, 1);
+
+        for hits_left in [2, 1] {
+            damage(&mut app, Damage::hit(enemy, laser));
+            app.update();
+            assert_eq!(app.world.get::<Health>(enemy), Some(&Health(hits_left)));
+        }
+        assert!(app.world.resource::<Deaths>().0.is_empty());
+
+        damage(&mut app, Damage::lethal(enemy));
+        app.update();
+        let deaths = &app.world.resource::<Deaths>().0;
+        assert_eq!(deaths.len(), 1);
+        assert_eq!(deaths[0].source, None);
+    }
+}
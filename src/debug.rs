@@ -0,0 +1,312 @@
+use crate::collision::{collider_size, CollisionLayer};
+use crate::components::{Enemy, Explosion, Laser, SpriteSize, Velocity};
+use crate::enemy::{Formation, FormationId};
+use crate::settings::KeyBindings;
+use crate::time::TimeScale;
+use crate::{EnemyCount, BASE_SPEED};
+use bevy::diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin};
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+
+const FONT: &str = "fonts/FiraMono-Medium.ttf";
+const TEXT_SIZE: f32 = 16.;
+const TEXT_COLOR: Color = Color::rgb(0.4, 1., 0.4);
+const LINE_WIDTH: f32 = 1.;
+const OVERLAY_Z: f32 = 50.;
+/// Seconds of travel the velocity vectors show
+const VECTOR_SECONDS: f32 = 0.1;
+/// Segments of a formation's orbit ellipse
+const ELLIPSE_SEGMENTS: usize = 48;
+const PIVOT_SIZE: f32 = 8.;
+const PATH_COLOR: Color = Color::rgba(1., 0.8, 0.2, 0.6);
+const VECTOR_COLOR: Color = Color::rgb(1., 1., 1.);
+
+/// Plugin - developer overlay, toggled with the debug key: collision boxes, formation orbits,
+/// laser velocities, entity counts and frame time. Also the keys freezing, stepping,
+/// slowing down and speeding up game time.
+pub struct DebugPlugin;
+
+impl Plugin for DebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DebugOverlay>()
+            .add_plugin(FrameTimeDiagnosticsPlugin)
+            .add_system(debug_toggle_system)
+            .add_system(time_controls_system)
+            // drawn where this frame left the entities
+            .add_system_to_stage(CoreStage::PostUpdate, debug_shapes_system)
+            .add_system_to_stage(CoreStage::PostUpdate, debug_text_system);
+    }
+}
+
+/// Resource - whether the overlay is shown
+#[derive(Debug, Default)]
+pub struct DebugOverlay {
+    pub enabled: bool,
+}
+
+/// Component - a shape of the overlay, redrawn every frame
+#[derive(Component)]
+struct DebugShape;
+
+/// Component - the counts and frame time
+#[derive(Component)]
+struct DebugText;
+
+fn debug_toggle_system(
+    keyboard: Res<Input<KeyCode>>,
+    keys: Res<KeyBindings>,
+    mut overlay: ResMut<DebugOverlay>,
+) {
+    if keyboard.just_pressed(keys.debug) {
+        overlay.enabled = !overlay.enabled;
+    }
+}
+
+fn time_controls_system(
+    keyboard: Res<Input<KeyCode>>,
+    keys: Res<KeyBindings>,
+    mut time_scale: ResMut<TimeScale>,
+) {
+    if keyboard.just_pressed(keys.freeze) {
+        time_scale.frozen = !time_scale.frozen;
+    }
+    if keyboard.just_pressed(keys.step) {
+        time_scale.step(1);
+    }
+    if keyboard.just_pressed(keys.slower) {
+        time_scale.slower();
+    }
+    if keyboard.just_pressed(keys.faster) {
+        time_scale.faster();
+    }
+}
+
+fn layer_color(layer: CollisionLayer) -> Color {
+    match layer {
+        CollisionLayer::Player => Color::rgb(0.2, 1., 0.2),
+        CollisionLayer::Enemy => Color::rgb(1., 0.2, 0.2),
+        CollisionLayer::PlayerLaser => Color::rgb(0.3, 0.8, 1.),
+        CollisionLayer::EnemyLaser => Color::rgb(1., 0.5, 0.1),
+        CollisionLayer::Shield => Color::rgb(0.6, 0.6, 0.6),
+    }
+}
+
+/// A straight line as a thin sprite
+fn spawn_line(commands: &mut Commands, from: Vec2, to: Vec2, color: Color) {
+    let delta = to - from;
+    let middle = from + delta / 2.;
+    let transform = Transform {
+        translation: middle.extend(OVERLAY_Z),
+        rotation: Quat::from_rotation_z(delta.y.atan2(delta.x)),
+        ..default()
+    };
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color,
+                custom_size: Some(Vec2::new(delta.length().max(LINE_WIDTH), LINE_WIDTH)),
+                ..default()
+            },
+            transform,
+            // spawned after this frame's transforms were propagated
+            global_transform: transform.into(),
+            ..default()
+        })
+        .insert(DebugShape);
+}
+
+fn spawn_box(commands: &mut Commands, center: Vec2, size: Vec2, color: Color) {
+    let half = size / 2.;
+    let corners = [
+        center + Vec2::new(-half.x, -half.y),
+        center + Vec2::new(half.x, -half.y),
+        center + Vec2::new(half.x, half.y),
+        center + Vec2::new(-half.x, half.y),
+    ];
+    for i in 0..corners.len() {
+        spawn_line(
+            commands,
+            corners[i],
+            corners[(i + 1) % corners.len()],
+            color,
+        );
+    }
+}
+
+fn spawn_ellipse(commands: &mut Commands, center: Vec2, radius: Vec2, color: Color) {
+    let point = |i: usize| {
+        let angle = i as f32 / ELLIPSE_SEGMENTS as f32 * std::f32::consts::TAU;
+        center + radius * Vec2::new(angle.cos(), angle.sin())
+    };
+    for i in 0..ELLIPSE_SEGMENTS {
+        spawn_line(commands, point(i), point(i + 1), color);
+    }
+}
+
+fn debug_shapes_system(
+    mut commands: Commands,
+    overlay: Res<DebugOverlay>,
+    shapes: Query<Entity, With<DebugShape>>,
+    colliders: Query<(&Transform, &SpriteSize, &CollisionLayer)>,
+    formations: Query<&Formation>,
+    lasers: Query<(&Transform, &Velocity), With<Laser>>,
+) {
+    for entity in shapes.iter() {
+        commands.entity(entity).despawn();
+    }
+    if !overlay.enabled {
+        return;
+    }
+
+    // what the collision systems see
+    for (transform, size, layer) in colliders.iter() {
+        let size = collider_size(transform, size);
+        spawn_box(
+            &mut commands,
+            transform.translation.truncate(),
+            size,
+            layer_color(*layer),
+        );
+    }
+
+    // orbit and pivot of each formation, once whatever its members
+    let mut drawn: HashSet<FormationId> = HashSet::default();
+    for formation in formations.iter() {
+        let data = &formation.data;
+        if !drawn.insert(data.id) {
+            continue;
+        }
+        let pivot = Vec2::new(data.pivot.0, data.pivot.1);
+        let radius = Vec2::new(data.radius.0, data.radius.1);
+        spawn_ellipse(&mut commands, pivot, radius, PATH_COLOR);
+        spawn_line(
+            &mut commands,
+            pivot - Vec2::X * PIVOT_SIZE,
+            pivot + Vec2::X * PIVOT_SIZE,
+            PATH_COLOR,
+        );
+        spawn_line(
+            &mut commands,
+            pivot - Vec2::Y * PIVOT_SIZE,
+            pivot + Vec2::Y * PIVOT_SIZE,
+            PATH_COLOR,
+        );
+    }
+
+    for (transform, velocity) in lasers.iter() {
+        let from = transform.translation.truncate();
+        let travel = Vec2::new(velocity.x, velocity.y) * BASE_SPEED * VECTOR_SECONDS;
+        spawn_line(&mut commands, from, from + travel, VECTOR_COLOR);
+    }
+}
+
+fn debug_text_system(
+    mut commands: Commands,
+    overlay: Res<DebugOverlay>,
+    asset_server: Option<Res<AssetServer>>,
+    diagnostics: Res<Diagnostics>,
+    enemy_count: Option<Res<EnemyCount>>,
+    time_scale: Res<TimeScale>,
+    enemies: Query<(), With<Enemy>>,
+    lasers: Query<(), With<Laser>>,
+    explosions: Query<(), With<Explosion>>,
+    mut texts: Query<(Entity, &mut Text), With<DebugText>>,
+) {
+    if !overlay.enabled {
+        for (entity, _) in texts.iter() {
+            commands.entity(entity).despawn();
+        }
+        return;
+    }
+
+    let frame_time = diagnostics
+        .get(FrameTimeDiagnosticsPlugin::FRAME_TIME)
+        .and_then(|diagnostic| diagnostic.average())
+        .unwrap_or_default();
+    let value = format!(
+        "enemies {} (EnemyCount {})  lasers {}  explosions {}\nframe {:.1} ms ({:.0} fps)  time {}x{}",
+        enemies.iter().count(),
+        enemy_count.map_or(0, |count| count.0),
+        lasers.iter().count(),
+        explosions.iter().count(),
+        frame_time * 1000.,
+        if frame_time > 0. { 1. / frame_time } else { 0. },
+        time_scale.scale,
+        if time_scale.frozen { " frozen" } else { "" },
+    );
+
+    if let Ok((_, mut text)) = texts.get_single_mut() {
+        text.sections[0].value = value;
+        return;
+    }
+
+    // nothing to show text with in headless runs
+    let asset_server = match asset_server {
+        Some(asset_server) => asset_server,
+        None => return,
+    };
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(6.),
+                    top: Val::Px(6.),
+                    ..default()
+                },
+                ..default()
+            },
+            text: Text::with_section(
+                value,
+                TextStyle {
+                    font: asset_server.load(FONT),
+                    font_size: TEXT_SIZE,
+                    color: TEXT_COLOR,
+                },
+                default(),
+            ),
+            ..default()
+        })
+        .insert(DebugText);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::Settings;
+    use crate::{headless_app, start_run};
+    use bevy::diagnostic::DiagnosticsPlugin;
+
+    #[test]
+    fn overlay_draws_only_when_enabled() {
+        let mut app = headless_app(Settings::default(), 1, 1);
+        // part of `DefaultPlugins`, which the overlay runs with
+        app.add_plugin(DiagnosticsPlugin).add_plugin(DebugPlugin);
+        start_run(&mut app);
+
+        let mut shapes = |app: &mut App| {
+            app.update();
+            app.world
+                .query_filtered::<(), With<DebugShape>>()
+                .iter(&app.world)
+                .count()
+        };
+        for _ in 0..120 {
+            assert_eq!(shapes(&mut app), 0);
+        }
+
+        app.world.resource_mut::<DebugOverlay>().enabled = true;
+        app.update();
+        let colliders = app
+            .world
+            .query::<(&SpriteSize, &CollisionLayer)>()
+            .iter(&app.world)
+            .count();
+        // the shapes of a frame are spawned after it despawned the last ones
+        assert!(shapes(&mut app) >= colliders * 4);
+
+        app.world.resource_mut::<DebugOverlay>().enabled = false;
+        app.update();
+        assert_eq!(shapes(&mut app), 0);
+    }
+}
@@ -0,0 +1,301 @@
+use crate::menu::MenuSystem;
+use crate::{
+    playing, NewGame, PlayerState, Wave, BASE_SPEED, ENEMY_FIRE_CHANCE, ENEMY_MAX,
+    FORMATION_MEMBERS_MAX, PLAYER_LIVES, PLAYER_RESPAWN_DELAY,
+};
+use bevy::prelude::*;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// Two deaths closer than this (in seconds) ease the adaptive mode by one level
+const ADAPTIVE_WINDOW: f64 = 20.;
+const ADAPTIVE_EASE_MAX: u32 = 3;
+/// Enemy speed and fire chance lost per ease level
+const ADAPTIVE_EASE_STEP: f32 = 0.15;
+
+/// Plugin - turns the selected difficulty into the `Tuning` of each run
+pub struct DifficultyPlugin;
+
+impl Plugin for DifficultyPlugin {
+    fn build(&self, app: &mut App) {
+        let difficulty = app
+            .world
+            .get_resource::<Difficulty>()
+            .copied()
+            .unwrap_or_default();
+        let adaptive = app
+            .world
+            .get_resource::<AdaptiveDifficulty>()
+            .copied()
+            .unwrap_or_default();
+
+        app.insert_resource(Tuning::new(difficulty, adaptive.0))
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                difficulty_new_game_system.after(MenuSystem),
+            )
+            // after the tick's gameplay, which may have killed the player or started a wave
+            .add_system_set_to_stage(
+                CoreStage::PostUpdate,
+                SystemSet::new()
+                    .with_run_criteria(playing)
+                    .with_system(adaptive_difficulty_system),
+            );
+    }
+}
+
+/// Resource - difficulty of new runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize, ValueEnum)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+    Insane,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 4] = [
+        Difficulty::Easy,
+        Difficulty::Normal,
+        Difficulty::Hard,
+        Difficulty::Insane,
+    ];
+
+    /// Next (or previous, for negative steps) difficulty, wrapping around
+    pub fn cycle(self, step: i32) -> Self {
+        let index = Self::ALL.iter().position(|d| *d == self).unwrap_or(0) as i32;
+        let len = Self::ALL.len() as i32;
+        Self::ALL[(index + step).rem_euclid(len) as usize]
+    }
+
+    pub fn preset(self) -> DifficultyPreset {
+        match self {
+            Difficulty::Easy => DifficultyPreset {
+                enemy_speed: BASE_SPEED * 0.75,
+                fire_chance: ENEMY_FIRE_CHANCE / 2.,
+                enemy_max: ENEMY_MAX,
+                formation_members_max: FORMATION_MEMBERS_MAX,
+                respawn_delay: PLAYER_RESPAWN_DELAY * 1.5,
+                lives: PLAYER_LIVES + 2,
+            },
+            Difficulty::Normal => DifficultyPreset {
+                enemy_speed: BASE_SPEED,
+                fire_chance: ENEMY_FIRE_CHANCE,
+                enemy_max: ENEMY_MAX,
+                formation_members_max: FORMATION_MEMBERS_MAX,
+                respawn_delay: PLAYER_RESPAWN_DELAY,
+                lives: PLAYER_LIVES,
+            },
+            Difficulty::Hard => DifficultyPreset {
+                enemy_speed: BASE_SPEED * 1.25,
+                fire_chance: ENEMY_FIRE_CHANCE * 1.5,
+                enemy_max: ENEMY_MAX + 1,
+                formation_members_max: FORMATION_MEMBERS_MAX + 1,
+                respawn_delay: PLAYER_RESPAWN_DELAY * 0.75,
+                lives: PLAYER_LIVES,
+            },
+            Difficulty::Insane => DifficultyPreset {
+                enemy_speed: BASE_SPEED * 1.5,
+                fire_chance: ENEMY_FIRE_CHANCE * 2.5,
+                enemy_max: ENEMY_MAX + 2,
+                formation_members_max: FORMATION_MEMBERS_MAX + 2,
+                respawn_delay: PLAYER_RESPAWN_DELAY * 0.5,
+                lives: PLAYER_LIVES - 1,
+            },
+        }
+    }
+}
+
+/// Resource - whether new runs ease off when the player keeps dying
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AdaptiveDifficulty(pub bool);
+
+/// Gameplay knobs of a difficulty
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DifficultyPreset {
+    pub enemy_speed: f32,           // of formations
+    pub fire_chance: f64,           // of the enemies firing, per frame
+    pub enemy_max: u32,             // enemies on screen
+    pub formation_members_max: u32, // enemies per formation
+    pub respawn_delay: f64,         // seconds
+    pub lives: u32,
+}
+
+/// Resource - knobs of the current run: the preset of its difficulty,
+/// eased by the adaptive mode
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tuning {
+    pub difficulty: Difficulty,
+    pub adaptive: bool,
+    pub ease: u32,
+    preset: DifficultyPreset,
+    last_death: Option<f64>,
+    last_wave: u32,
+}
+
+impl Tuning {
+    pub fn new(difficulty: Difficulty, adaptive: bool) -> Self {
+        Self {
+            difficulty,
+            adaptive,
+            ease: 0,
+            preset: difficulty.preset(),
+            last_death: None,
+            last_wave: 0,
+        }
+    }
+
+    fn eased(&self) -> f32 {
+        1. - ADAPTIVE_EASE_STEP * self.ease as f32
+    }
+
+    pub fn enemy_speed(&self) -> f32 {
+        self.preset.enemy_speed * self.eased()
+    }
+
+    pub fn fire_chance(&self) -> f64 {
+        self.preset.fire_chance * self.eased() as f64
+    }
+
+    pub fn enemy_max(&self) -> u32 {
+        self.preset.enemy_max
+    }
+
+    pub fn formation_members_max(&self) -> u32 {
+        self.preset.formation_members_max
+    }
+
+    pub fn respawn_delay(&self) -> f64 {
+        self.preset.respawn_delay
+    }
+}
+
+fn difficulty_new_game_system(
+    mut tuning: ResMut<Tuning>,
+    difficulty: Res<Difficulty>,
+    adaptive: Res<AdaptiveDifficulty>,
+    mut events: EventReader<NewGame>,
+) {
+    if events.iter().count() > 0 {
+        *tuning = Tuning::new(*difficulty, adaptive.0);
+    }
+}
+
+/// Eases one level when the player dies twice within `ADAPTIVE_WINDOW`,
+/// and gets one level back with every new wave
+fn adaptive_difficulty_system(
+    mut tuning: ResMut<Tuning>,
+    player_state: Res<PlayerState>,
+    wave: Res<Wave>,
+) {
+    if !tuning.adaptive {
+        return;
+    }
+
+    let death = player_state.last_shot;
+    if !player_state.on && death >= 0. && tuning.last_death != Some(death) {
+        if let Some(last_death) = tuning.last_death {
+            if death - last_death < ADAPTIVE_WINDOW {
+                tuning.ease = (tuning.ease + 1).min(ADAPTIVE_EASE_MAX);
+            }
+        }
+        tuning.last_death = Some(death);
+    }
+
+    // tracked rather than `is_changed` so restoring a saved run does not count as a new wave
+    if wave.0 != tuning.last_wave {
+        if tuning.last_wave != 0 && tuning.ease > 0 {
+            tuning.ease -= 1;
+        }
+        tuning.last_wave = wave.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::Cli;
+    use clap::Parser;
+
+    fn app(adaptive: bool) -> App {
+        let mut player_state = PlayerState {
+            lives: 10,
+            ..default()
+        };
+        player_state.spawned();
+
+        let mut app = App::new();
+        app.insert_resource(Tuning::new(Difficulty::Normal, adaptive))
+            .insert_resource(player_state)
+            .insert_resource(Wave(1))
+            .add_system(adaptive_difficulty_system);
+        app.update();
+        app
+    }
+
+    fn die_at(app: &mut App, time: f64) -> u32 {
+        app.world.resource_mut::<PlayerState>().shot(time);
+        app.update();
+        app.world.resource_mut::<PlayerState>().spawned();
+        app.update();
+        app.world.resource::<Tuning>().ease
+    }
+
+    fn next_wave(app: &mut App) -> u32 {
+        app.world.resource_mut::<Wave>().0 += 1;
+        app.update();
+        app.world.resource::<Tuning>().ease
+    }
+
+    #[test]
+    fn close_deaths_ease_up_to_the_max() {
+        let mut app = app(true);
+        assert_eq!(die_at(&mut app, 10.), 0);
+        assert_eq!(die_at(&mut app, 10. + ADAPTIVE_WINDOW - 1.), 1);
+        // too far apart
+        assert_eq!(die_at(&mut app, 10. + ADAPTIVE_WINDOW * 3.), 1);
+
+        let preset = Difficulty::Normal.preset();
+        let tuning = app.world.resource::<Tuning>();
+        let eased = 1. - ADAPTIVE_EASE_STEP;
+        assert!((tuning.enemy_speed() - preset.enemy_speed * eased).abs() < 1e-4);
+        assert!((tuning.fire_chance() - preset.fire_chance * eased as f64).abs() < 1e-6);
+        assert_eq!(tuning.enemy_max(), preset.enemy_max);
+
+        for time in [100., 105., 110., 115., 120.] {
+            die_at(&mut app, time);
+        }
+        assert_eq!(app.world.resource::<Tuning>().ease, ADAPTIVE_EASE_MAX);
+    }
+
+    #[test]
+    fn new_waves_take_the_ease_back() {
+        let mut app = app(true);
+        for time in [10., 12., 14.] {
+            die_at(&mut app, time);
+        }
+        assert_eq!(app.world.resource::<Tuning>().ease, 2);
+
+        assert_eq!(next_wave(&mut app), 1);
+        assert_eq!(next_wave(&mut app), 0);
+        assert_eq!(next_wave(&mut app), 0);
+    }
+
+    #[test]
+    fn only_the_adaptive_mode_eases() {
+        let mut app = app(false);
+        for time in [10., 12., 14.] {
+            assert_eq!(die_at(&mut app, time), 0);
+        }
+    }
+
+    #[test]
+    fn command_line_picks_the_difficulty() {
+        let cli =
+            Cli::try_parse_from(["rust-invaders", "--difficulty", "hard", "--adaptive"]).unwrap();
+        assert_eq!(cli.difficulty, Some(Difficulty::Hard));
+        assert!(cli.adaptive);
+        assert!(Cli::try_parse_from(["rust-invaders", "--difficulty", "brutal"]).is_err());
+    }
+}
@@ -0,0 +1,408 @@
+use crate::animation::{AnimationClip, AnimationMode, SpriteAnimation};
+use crate::audio::{PlaySound, Sound};
+use crate::collision::CollisionLayer;
+use crate::components::{Explosion, ExplosionSize, ExplosionToSpawn, MainCamera, Particle};
+use crate::damage::{DamageSystem, Death};
+use crate::settings::load_ron;
+use crate::{playing, GameTextures, EXPLOSION_LEN, TIME_STEP};
+use bevy::prelude::*;
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Side of a frame of the explosion sheet, in pixels
+const EXPLOSION_FRAME: f32 = 64.;
+
+/// Plugin - explosions sized per victim, their debris, and the screen shake of the player's death
+pub struct EffectsPlugin;
+
+impl Plugin for EffectsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EffectsConfig>()
+            .init_resource::<ScreenShake>()
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(playing)
+                    .with_system(death_explosion_system.after(DamageSystem))
+                    .with_system(explosion_to_spawn_system)
+                    .with_system(particle_system),
+            )
+            // in real time, it goes on over the game over screen
+            .add_system(screen_shake_system);
+    }
+}
+
+/// Resource - look of the effects, loaded from `EFFECTS_FILE` by windowed runs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EffectsConfig {
+    pub small: ExplosionStyle,
+    pub medium: ExplosionStyle,
+    pub big: ExplosionStyle,
+    pub shake: ShakeStyle,
+}
+
+impl Default for EffectsConfig {
+    fn default() -> Self {
+        Self {
+            small: ExplosionStyle {
+                scale: 0.4,
+                clip: ExplosionStyle::clip(33.3),
+                sound: false,
+                debris: 4,
+                debris_speed: (40., 120.),
+                debris_lifetime: 0.3,
+                debris_size: 3.,
+                debris_color: Color::rgb(1., 0.85, 0.4),
+            },
+            medium: ExplosionStyle::default(),
+            big: ExplosionStyle {
+                scale: 1.6,
+                clip: ExplosionStyle::clip(16.67),
+                sound: true,
+                debris: 24,
+                debris_speed: (80., 320.),
+                debris_lifetime: 0.9,
+                debris_size: 5.,
+                debris_color: Color::rgb(0.6, 0.9, 1.),
+            },
+            shake: ShakeStyle::default(),
+        }
+    }
+}
+
+impl EffectsConfig {
+    /// Defaults if the file is missing or unreadable
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        if !path.exists() {
+            warn!("{} not found, using the default effects", path.display());
+        }
+        load_ron(path).map_or_else(Self::default, Self::checked)
+    }
+
+    /// Clips that go past the explosion sheet, or would never finish, are replaced by
+    /// their default, the rest of their style is kept
+    fn checked(mut self) -> Self {
+        let defaults = Self::default();
+        for (name, style, default) in [
+            ("small", &mut self.small, defaults.small),
+            ("medium", &mut self.medium, defaults.medium),
+            ("big", &mut self.big, defaults.big),
+        ] {
+            if style.clip.last >= EXPLOSION_LEN {
+                warn!(
+                    "{} explosion clip ends past the {} frames of the sheet, using the default clip",
+                    name, EXPLOSION_LEN
+                );
+                style.clip = default.clip;
+            } else if style.clip.mode != AnimationMode::Once {
+                warn!(
+                    "{} explosion clip would never finish, using the default clip",
+                    name
+                );
+                style.clip = default.clip;
+            }
+        }
+        self
+    }
+
+    pub fn style(&self, size: ExplosionSize) -> &ExplosionStyle {
+        match size {
+            ExplosionSize::Small => &self.small,
+            ExplosionSize::Medium => &self.medium,
+            ExplosionSize::Big => &self.big,
+        }
+    }
+}
+
+/// An explosion variant: its sprite sheet animation and its debris
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExplosionStyle {
+    /// of the sheet frames
+    pub scale: f32,
+    /// played `Once`, the explosion is gone once it finished
+    pub clip: AnimationClip,
+    pub sound: bool,
+    /// pieces of debris thrown around
+    pub debris: u32,
+    /// range of the speeds of the pieces, in pixels per second
+    pub debris_speed: (f32, f32),
+    /// seconds a piece takes to fade out
+    pub debris_lifetime: f32,
+    pub debris_size: f32,
+    pub debris_color: Color,
+}
+
+impl ExplosionStyle {
+    /// The whole explosion sheet, once
+    fn clip(fps: f32) -> AnimationClip {
+        AnimationClip::new(0, EXPLOSION_LEN - 1, fps, AnimationMode::Once)
+    }
+}
+
+impl Default for ExplosionStyle {
+    fn default() -> Self {
+        Self {
+            scale: 1.,
+            clip: ExplosionStyle::clip(20.),
+            sound: true,
+            debris: 12,
+            debris_speed: (60., 220.),
+            debris_lifetime: 0.6,
+            debris_size: 4.,
+            debris_color: Color::rgb(1., 0.6, 0.2),
+        }
+    }
+}
+
+/// How hard and how long the screen shakes when the player dies
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShakeStyle {
+    /// largest offset of the camera, in pixels
+    pub amplitude: f32,
+    pub seconds: f32,
+}
+
+impl Default for ShakeStyle {
+    fn default() -> Self {
+        Self {
+            amplitude: 12.,
+            seconds: 0.4,
+        }
+    }
+}
+
+/// Resource - what is left of the current screen shake
+#[derive(Debug, Default)]
+pub struct ScreenShake {
+    left: f32,
+    style: ShakeStyle,
+}
+
+impl ScreenShake {
+    pub fn start(&mut self, style: &ShakeStyle) {
+        self.left = style.seconds;
+        self.style = style.clone();
+    }
+
+    pub fn is_shaking(&self) -> bool {
+        self.left > 0.
+    }
+
+    /// Offset of the camera, dying down with the shake
+    fn offset(&self, rng: &mut impl Rng) -> Vec2 {
+        if !self.is_shaking() {
+            return Vec2::ZERO;
+        }
+        let strength = (self.left / self.style.seconds).powi(2);
+        Vec2::new(rng.gen_range(-1. ..=1.), rng.gen_range(-1. ..=1.))
+            * self.style.amplitude
+            * strength
+    }
+}
+
+impl ExplosionSize {
+    /// Small for lasers and shields, big for the player
+    pub fn of(layer: CollisionLayer) -> Self {
+        match layer {
+            CollisionLayer::Player => ExplosionSize::Big,
+            CollisionLayer::Enemy => ExplosionSize::Medium,
+            CollisionLayer::PlayerLaser | CollisionLayer::EnemyLaser | CollisionLayer::Shield => {
+                ExplosionSize::Small
+            }
+        }
+    }
+}
+
+fn death_explosion_system(
+    mut commands: Commands,
+    mut deaths: EventReader<Death>,
+    config: Res<EffectsConfig>,
+    mut shake: ResMut<ScreenShake>,
+) {
+    for death in deaths.iter() {
+        commands.spawn().insert(ExplosionToSpawn {
+            translation: death.translation,
+            size: ExplosionSize::of(death.layer),
+        });
+        if death.layer == CollisionLayer::Player {
+            shake.start(&config.shake);
+        }
+    }
+}
+
+fn explosion_to_spawn_system(
+    mut commands: Commands,
+    game_textures: Res<GameTextures>,
+    config: Res<EffectsConfig>,
+    query: Query<(Entity, &ExplosionToSpawn)>,
+    mut sounds: EventWriter<PlaySound>,
+) {
+    // cosmetic only, the game rng is left alone for runs to stay reproducible
+    let mut rng = thread_rng();
+
+    for (explosion_to_spawn_entity, explosion_to_spawn) in query.iter() {
+        let style = config.style(explosion_to_spawn.size);
+        let animation = SpriteAnimation::new(style.clip.clone()).despawn_when_finished();
+        commands
+            .spawn_bundle(SpriteSheetBundle {
+                sprite: TextureAtlasSprite::new(animation.index()),
+                texture_atlas: game_textures.explosion.clone(),
+                transform: Transform {
+                    translation: explosion_to_spawn.translation,
+                    scale: Vec3::new(style.scale, style.scale, 1.),
+                    ..default()
+                },
+                ..default()
+            })
+            .insert(Explosion)
+            .insert(animation);
+        if style.sound {
+            sounds.send(PlaySound(Sound::Explosion));
+        }
+
+        // above the explosion, thrown from anywhere within it
+        let reach = EXPLOSION_FRAME * style.scale / 4.;
+        for _ in 0..style.debris {
+            let angle: f32 = rng.gen_range(0. ..std::f32::consts::TAU);
+            let direction = Vec2::new(angle.cos(), angle.sin());
+            let (min_speed, max_speed) = style.debris_speed;
+            let speed = rng.gen_range(min_speed..=max_speed.max(min_speed));
+            let translation = explosion_to_spawn.translation
+                + (direction * rng.gen_range(0. ..=reach)).extend(1.);
+            commands
+                .spawn_bundle(SpriteBundle {
+                    sprite: Sprite {
+                        color: style.debris_color,
+                        custom_size: Some(Vec2::splat(style.debris_size)),
+                        ..default()
+                    },
+                    transform: Transform::from_translation(translation),
+                    ..default()
+                })
+                .insert(Particle {
+                    velocity: direction * speed,
+                    age: 0.,
+                    lifetime: style.debris_lifetime,
+                });
+        }
+
+        commands.entity(explosion_to_spawn_entity).despawn();
+    }
+}
+
+fn particle_system(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Particle, &mut Transform, &mut Sprite)>,
+) {
+    for (entity, mut particle, mut transform, mut sprite) in query.iter_mut() {
+        particle.age += TIME_STEP;
+        if particle.age >= particle.lifetime {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        transform.translation += (particle.velocity * TIME_STEP).extend(0.);
+        sprite.color.set_a(1. - particle.age / particle.lifetime);
+    }
+}
+
+fn screen_shake_system(
+    time: Res<Time>,
+    mut shake: ResMut<ScreenShake>,
+    mut cameras: Query<&mut Transform, With<MainCamera>>,
+) {
+    if !shake.is_shaking() {
+        return;
+    }
+
+    // back in place the frame it ends
+    shake.left = (shake.left - time.delta_seconds()).max(0.);
+    let offset = shake.offset(&mut thread_rng());
+    for mut transform in cameras.iter_mut() {
+        transform.translation.x = offset.x;
+        transform.translation.y = offset.y;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::Settings;
+    use crate::{headless_app, start_run};
+    use bevy::ecs::event::Events;
+
+    #[test]
+    fn shipped_config_parses() {
+        let path = crate::shipped_file(crate::EFFECTS_FILE);
+        let text = std::fs::read_to_string(path).unwrap();
+        ron::from_str::<EffectsConfig>(&text).unwrap();
+
+        // missing parts are filled in
+        let config: EffectsConfig = ron::from_str("(shake: (amplitude: 3.))").unwrap();
+        assert_eq!(config.shake.amplitude, 3.);
+        assert_eq!(config.shake.seconds, ShakeStyle::default().seconds);
+        assert_eq!(config.big, EffectsConfig::default().big);
+    }
+
+    #[test]
+    fn unplayable_clips_fall_back_to_the_default_clip() {
+        let path = std::env::temp_dir().join("rust_invaders_long_clip.ron");
+        std::fs::write(
+            &path,
+            "(
+                small: (scale: 2.),
+                medium: (clip: (first: 0, last: 15, fps: 10., mode: Loop)),
+                big: (scale: 3., clip: (first: 0, last: 16, fps: 10., mode: Once)),
+            )",
+        )
+        .unwrap();
+        let config = EffectsConfig::load(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let defaults = EffectsConfig::default();
+        assert_eq!(config.small.scale, 2.);
+        assert_eq!(config.medium.clip, defaults.medium.clip);
+        assert_eq!(config.big.clip, defaults.big.clip);
+        assert_eq!(config.big.scale, 3.);
+    }
+
+    #[test]
+    fn player_death_explodes_big_and_shakes() {
+        let mut app = headless_app(Settings::default(), 1, 1);
+        start_run(&mut app);
+        app.update();
+
+        app.world.resource_mut::<Events<Death>>().send(Death {
+            entity: Entity::from_raw(u32::MAX),
+            layer: CollisionLayer::Player,
+            translation: Vec3::new(0., -300., 10.),
+            source: None,
+        });
+        app.update();
+        assert!(app.world.resource::<ScreenShake>().is_shaking());
+        app.update();
+
+        let big = EffectsConfig::default().big;
+        let scale = app
+            .world
+            .query_filtered::<&Transform, With<Explosion>>()
+            .iter(&app.world)
+            .map(|transform| transform.scale.x)
+            .next();
+        assert_eq!(scale, Some(big.scale));
+
+        let mut particles = app.world.query::<&Particle>();
+        assert_eq!(particles.iter(&app.world).count(), big.debris as usize);
+
+        // all faded out and gone in time
+        let ticks = (big.debris_lifetime / TIME_STEP).ceil() as usize + 1;
+        for _ in 0..ticks {
+            app.update();
+        }
+        assert_eq!(particles.iter(&app.world).count(), 0);
+    }
+}
@@ -0,0 +1,143 @@
+use crate::enemy::formation::Formation;
+use crate::{Enemy, GameRng, Player, WinSize, ENEMY_DIVE_SPEED, TIME_STEP};
+use bevy::prelude::*;
+use rand::seq::IteratorRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// Component - Enemy that left its formation orbit to dive at the player.
+/// Follows a quadratic Bezier curve from `from` to `to`, bending through `control`.
+#[derive(Component, Clone, Serialize, Deserialize)]
+pub struct Dive {
+    pub from: Vec2,
+    pub control: Vec2,
+    pub to: Vec2,
+    pub progress: f32,   // 0. -> 1.
+    pub returning: bool, // back to formation when done, otherwise leaves the screen
+}
+
+impl Dive {
+    fn point(&self, t: f32) -> Vec2 {
+        let u = 1. - t;
+        self.from * u * u + self.control * 2. * u * t + self.to * t * t
+    }
+
+    fn length(&self) -> f32 {
+        // control polygon length, good enough to keep a steady pace
+        self.from.distance(self.control) + self.control.distance(self.to)
+    }
+}
+
+pub fn enemy_dive_start_system(
+    mut commands: Commands,
+    win_size: Res<WinSize>,
+    mut rng: ResMut<GameRng>,
+    player_query: Query<&Transform, With<Player>>,
+    enemy_query: Query<(Entity, &Transform, &Formation), (With<Enemy>, Without<Dive>)>,
+) {
+    let player_transform = match player_query.get_single() {
+        Ok(transform) => transform,
+        Err(_) => return,
+    };
+
+    let rng = &mut *rng;
+    let candidates = enemy_query
+        .iter()
+        .filter(|(_, _, formation)| formation.delay <= 0.);
+
+    if let Some((entity, transform, _)) = candidates.choose(rng) {
+        let from = transform.translation.truncate();
+        let returning = rng.gen_bool(0.5);
+
+        // aim through the player's current position, then past it
+        let target = player_transform.translation.truncate();
+        let to = if returning {
+            Vec2::new(target.x, target.y - 50.)
+        } else {
+            Vec2::new(target.x, -win_size.height / 2. - 100.)
+        };
+
+        // swing out to one side on the way down
+        let side = if rng.gen_bool(0.5) { 1. } else { -1. };
+        let control = Vec2::new(from.x + side * 200., (from.y + to.y) / 2.);
+
+        commands.entity(entity).insert(Dive {
+            from,
+            control,
+            to,
+            progress: 0.,
+            returning,
+        });
+    }
+}
+
+pub fn enemy_dive_system(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Transform, &mut Dive), With<Enemy>>,
+) {
+    for (entity, mut transform, mut dive) in query.iter_mut() {
+        let length = dive.length().max(1.);
+        dive.progress = (dive.progress + ENEMY_DIVE_SPEED * TIME_STEP / length).min(1.);
+
+        let point = dive.point(dive.progress);
+        (transform.translation.x, transform.translation.y) = (point.x, point.y);
+
+        if dive.progress >= 1. {
+            if dive.returning {
+                // enemy_move_system takes over and flies it back to its formation
+                commands.entity(entity).remove::<Dive>();
+            } else {
+                commands.entity(entity).despawn();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::PreviousTranslation;
+    use crate::settings::Settings;
+    use crate::{headless_app, start_run, EnemyCount};
+
+    #[test]
+    fn ramming_on_the_way_out_removes_the_enemy_once() {
+        let mut app = headless_app(Settings::default(), 1, 1);
+        start_run(&mut app);
+
+        let mut players = app.world.query_filtered::<&Transform, With<Player>>();
+        let mut enemies = app.world.query_filtered::<Entity, With<Enemy>>();
+        let (target, enemy) = loop {
+            app.update();
+            let player = players.iter(&app.world).next().map(|t| t.translation);
+            let enemy = enemies.iter(&app.world).next();
+            if let (Some(player), Some(enemy)) = (player, enemy) {
+                break (player, enemy);
+            }
+            assert!(app.world.resource::<crate::GameClock>().tick < 300);
+        };
+
+        // the last step of a dive that leaves the screen, right through the player
+        let point = target.truncate();
+        app.world
+            .entity_mut(enemy)
+            .insert(Dive {
+                from: point,
+                control: point,
+                to: point,
+                progress: 0.999,
+                returning: false,
+            })
+            .insert(Transform::from_translation(target))
+            .insert(PreviousTranslation(target));
+        let before = enemies.iter(&app.world).count() as u32;
+        assert_eq!(app.world.resource::<EnemyCount>().0, before);
+
+        app.update();
+        // rammed, and gone off the dive the same tick
+        assert_eq!(players.iter(&app.world).count(), 0);
+        assert!(app.world.get_entity(enemy).is_none());
+        assert_eq!(enemies.iter(&app.world).count() as u32, before - 1);
+        assert_eq!(app.world.resource::<EnemyCount>().0, before - 1);
+    }
+}
@@ -1,60 +1,201 @@
 use std::f32::consts::PI;
-use bevy::core::FixedTimestep;
 use bevy::ecs::schedule::ShouldRun;
-use rand::{Rng, thread_rng};
-use crate::{App, Commands, default, Enemy, ENEMY_LASER_SIZE, ENEMY_MAX, ENEMY_SIZE, EnemyCount, FromEnemy, GameTextures, Laser, Movable, Plugin, Quat, Query, Res, ResMut, SPRITE_SCALE, SpriteBundle, SpriteSize, SystemSet, Time, TIME_STEP, Transform, Vec3, Velocity, WinSize, With};
-use crate::enemy::formation::{Formation, FormationMaker};
+use bevy::ecs::system::CommandQueue;
+use bevy::prelude::{Vec2, World};
+use rand::Rng;
+use crate::{every, movable_system, GameClock, playing, while_playing, GameRng, App, Commands, CoreStage, default, Enemy, EnemyCount, ENEMY_DIVE_INTERVAL, ENEMY_HEALTH, ENEMY_LASER_SIZE, ENEMY_SIZE, Entity, EventReader, EventWriter, FromEnemy, In, IntoChainSystem, NewGame, GameTextures, Laser, Movable, ParallelSystemDescriptorCoercion, Plugin, PreviousTranslation, Quat, Query, RemovedComponents, Res, ResMut, SpriteBundle, SPRITE_SCALE, SpriteSize, SystemSet, TIME_STEP, Transform, Vec3, Velocity, WinSize, With, Without};
+use crate::audio::{PlaySound, Sound};
+use crate::collision::{CollisionLayer, CollisionSystem};
+use crate::damage::{DamageSystem, Death, Health};
+use crate::enemy::dive::{enemy_dive_start_system, enemy_dive_system, Dive};
+use crate::difficulty::Tuning;
+use crate::menu::MenuSystem;
+use crate::enemy::formation::{
+    FiredBy, Formation, FormationCleared, FormationMaker, FormationRegistry,
+};
 
 pub struct EnemyPlugin;
 
 impl Plugin for EnemyPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(FormationMaker::default())
+            .insert_resource(FormationRegistry::default())
+            .add_event::<FormationCleared>()
             .add_system_set(
                 SystemSet::new()
-                    .with_run_criteria(FixedTimestep::step(1.))
-                    .with_system(enemy_spawn_system),
+                    .with_run_criteria(every(1.).chain(while_playing))
+                    // both spawn or despawn through commands: in a fixed order, entity ids and
+                    // with them the query order are the same whatever thread runs them
+                    .with_system(enemy_spawn_system.after(enemy_dive_system)),
             )
             .add_system_set(
                 SystemSet::new()
-                    .with_run_criteria(enemy_fire_criteria)
-                    .with_system(enemy_fire_system),
+                    .with_run_criteria(playing.chain(enemy_fire_criteria))
+                    .with_system(
+                        enemy_fire_system
+                            .before(enemy_move_system)
+                            .before(enemy_dive_system),
+                    ),
             )
-            .add_system(enemy_move_system);
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(every(ENEMY_DIVE_INTERVAL).chain(while_playing))
+                    // both roll the game rng, in a fixed order for runs to be reproducible
+                    .with_system(
+                        enemy_dive_start_system
+                            .after(enemy_spawn_system)
+                            .before(enemy_move_system)
+                            .before(movable_system),
+                    ),
+            )
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(playing)
+                    .with_system(enemy_move_system.before(CollisionSystem::Broadphase))
+                    .with_system(enemy_dive_system.before(CollisionSystem::Broadphase))
+                    .with_system(formation_kill_system.after(DamageSystem)),
+            )
+            .add_system_to_stage(CoreStage::PreUpdate, enemy_new_game_system.after(MenuSystem))
+            .add_system_to_stage(CoreStage::PostUpdate, formation_removal_system);
     }
 }
 
 fn enemy_spawn_system(
     mut commands: Commands,
-    mut enemy_count: ResMut<EnemyCount>,
+    enemy_count: Res<EnemyCount>,
     mut formation_maker: ResMut<FormationMaker>,
+    mut formation_registry: ResMut<FormationRegistry>,
+    tuning: Res<Tuning>,
+    mut rng: ResMut<GameRng>,
+    clock: Res<GameClock>,
     game_textures: Res<GameTextures>,
     win_size: Res<WinSize>,
 ) {
-    if enemy_count.0 < ENEMY_MAX {
-        let formation = formation_maker.make(&win_size);
+    // spawn the members of a formation together, their entry delays keep them apart
+    let mut alive = enemy_count.0;
+    while alive < tuning.enemy_max() {
+        let formation = match formation_maker.next_member(clock.tick) {
+            Some(formation) => formation,
+            None => {
+                let members = rng.gen_range(1..=tuning.formation_members_max());
+                formation_maker.start(members, &win_size, &tuning, &mut rng, clock.tick)
+            }
+        };
         let (x, y) = formation.data.start;
+        let translation = Vec3::new(x, y, 10.);
 
-        commands
-            .spawn_bundle(SpriteBundle {
-                texture: game_textures.enemy.clone(),
-                transform: Transform {
-                    translation: Vec3::new(x, y, 10.),
-                    scale: Vec3::new(SPRITE_SCALE, SPRITE_SCALE, 1.),
-                    ..default()
-                },
+        let entity = spawn_enemy(&mut commands, &game_textures, translation, formation.clone());
+        formation_registry.spawned(entity, &formation.data);
+
+        alive += 1;
+
+        if formation_maker.members_left() == 0 {
+            break;
+        }
+    }
+}
+
+pub fn spawn_enemy(
+    commands: &mut Commands,
+    game_textures: &GameTextures,
+    translation: Vec3,
+    formation: Formation,
+) -> Entity {
+    commands
+        .spawn_bundle(SpriteBundle {
+            texture: game_textures.enemy.clone(),
+            transform: Transform {
+                translation,
+                scale: Vec3::new(SPRITE_SCALE, SPRITE_SCALE, 1.),
                 ..default()
-            })
-            .insert(Enemy)
-            .insert(formation)
-            .insert(SpriteSize::from(ENEMY_SIZE));
+            },
+            ..default()
+        })
+        .insert(Enemy)
+        .insert(CollisionLayer::Enemy)
+        .insert(PreviousTranslation(translation))
+        .insert(formation)
+        .insert(SpriteSize::from(ENEMY_SIZE))
+        .insert(Health(ENEMY_HEALTH))
+        .id()
+}
 
-        enemy_count.0 += 1;
+/// Spawns the members of a formation made to order (developer console),
+/// see `FormationMaker::custom`
+pub fn spawn_custom_formation(
+    world: &mut World,
+    start: Vec2,
+    pivot: Vec2,
+    radius: Vec2,
+    members: u32,
+) {
+    let speed = world.resource::<Tuning>().enemy_speed();
+    let tick = world.resource::<GameClock>().tick;
+    let formation = world
+        .resource_mut::<FormationMaker>()
+        .custom(start, pivot, radius, members, speed, tick);
+
+    let mut queue = CommandQueue::default();
+    let mut commands = Commands::new(&mut queue, world);
+    let textures = world.resource::<GameTextures>();
+    let members: Vec<Entity> = (0..formation.data.members)
+        .map(|slot| {
+            let mut member = formation.clone();
+            member.slot = slot;
+            member.delay = slot as f32 * formation.data.entry_delay;
+            spawn_enemy(&mut commands, textures, start.extend(10.), member)
+        })
+        .collect();
+    queue.apply(world);
+
+    let mut registry = world.resource_mut::<FormationRegistry>();
+    for entity in members.iter() {
+        registry.spawned(*entity, &formation.data);
+    }
+}
+
+fn enemy_new_game_system(
+    mut formation_maker: ResMut<FormationMaker>,
+    mut formation_registry: ResMut<FormationRegistry>,
+    mut events: EventReader<NewGame>,
+) {
+    if events.iter().count() > 0 {
+        *formation_maker = FormationMaker::default();
+        *formation_registry = FormationRegistry::default();
+    }
+}
+
+fn formation_kill_system(
+    mut formation_registry: ResMut<FormationRegistry>,
+    mut deaths: EventReader<Death>,
+) {
+    for death in deaths.iter() {
+        if let Some((_, CollisionLayer::PlayerLaser)) = death.source {
+            formation_registry.killed(death.entity);
+        }
+    }
+}
+
+/// Runs after despawns of the frame are applied, whatever caused them
+fn formation_removal_system(
+    mut formation_registry: ResMut<FormationRegistry>,
+    removed: RemovedComponents<Formation>,
+    mut cleared: EventWriter<FormationCleared>,
+) {
+    for entity in removed.iter() {
+        if let Some(event) = formation_registry.removed(entity) {
+            cleared.send(event);
+        }
     }
 }
 
-fn enemy_fire_criteria() -> ShouldRun {
-    if thread_rng().gen_bool(1. / 60.) {
+/// Chained after `playing`, so the dice are only rolled on game ticks
+fn enemy_fire_criteria(
+    In(playing): In<ShouldRun>,
+    tuning: Res<Tuning>,
+    mut rng: ResMut<GameRng>,
+) -> ShouldRun {
+    if playing == ShouldRun::Yes && rng.gen_bool(tuning.fire_chance().min(1.)) {
         ShouldRun::Yes
     } else {
         ShouldRun::No
@@ -64,77 +205,175 @@ fn enemy_fire_criteria() -> ShouldRun {
 fn enemy_fire_system(
     mut commands: Commands,
     game_textures: Res<GameTextures>,
-    enemy_query: Query<&Transform, With<Enemy>>,
+    enemy_query: Query<(&Transform, &Formation), With<Enemy>>,
+    mut sounds: EventWriter<PlaySound>,
 ) {
-    for transform in enemy_query.iter() {
+    if !enemy_query.is_empty() {
+        sounds.send(PlaySound(Sound::EnemyLaser));
+    }
+
+    for (transform, formation) in enemy_query.iter() {
         let (x, y) = (transform.translation.x, transform.translation.y);
-        // spawn enemy laser sprite
+        let laser = spawn_enemy_laser(&mut commands, &game_textures, Vec3::new(x, y - 15., 0.));
         commands
-            .spawn_bundle(SpriteBundle {
-                texture: game_textures.enemy_laser.clone(),
-                transform: Transform {
-                    translation: Vec3::new(x, y - 15., 0.),
-                    rotation: Quat::from_rotation_x(PI),
-                    scale: Vec3::new(SPRITE_SCALE, SPRITE_SCALE, 1.),
-                    ..default()
-                },
-                ..default()
-            })
-            .insert(Laser)
-            .insert(SpriteSize::from(ENEMY_LASER_SIZE))
-            .insert(FromEnemy)
-            .insert(Movable { auto_despawn: true })
-            .insert(Velocity { x: 0., y: -1. });
+            .entity(laser)
+            .insert(FiredBy(formation.data.clone()));
     }
 }
 
+pub fn spawn_enemy_laser(
+    commands: &mut Commands,
+    game_textures: &GameTextures,
+    translation: Vec3,
+) -> Entity {
+    commands
+        .spawn_bundle(SpriteBundle {
+            texture: game_textures.enemy_laser.clone(),
+            transform: Transform {
+                translation,
+                rotation: Quat::from_rotation_x(PI),
+                scale: Vec3::new(SPRITE_SCALE, SPRITE_SCALE, 1.),
+            },
+            ..default()
+        })
+        .insert(Laser)
+        .insert(SpriteSize::from(ENEMY_LASER_SIZE))
+        .insert(FromEnemy)
+        .insert(CollisionLayer::EnemyLaser)
+        .insert(PreviousTranslation(translation))
+        .insert(Movable { auto_despawn: true })
+        .insert(Velocity { x: 0., y: -1. })
+        .id()
+}
+
 fn enemy_move_system(
-    time: Res<Time>,
-    mut query: Query<(&mut Transform, &mut Formation), With<Enemy>>,
+    mut query: Query<(&mut Transform, &mut Formation), (With<Enemy>, Without<Dive>)>,
 ) {
-    let now = time.seconds_since_startup() as f32;
-
     for (mut transform, mut formation) in query.iter_mut() {
+        // wait for this member's turn to enter
+        if formation.delay > 0. {
+            formation.delay -= TIME_STEP;
+            continue;
+        }
+
         // current position
-        let (x_original, y_original) = (transform.translation.x, transform.translation.y);
+        let current = transform.translation.truncate();
 
         // max distance
         let max_distance = TIME_STEP * formation.data.speed;
 
-        // fixtures
-        let dir: f32 = if formation.data.start.0 < 0. {-1.} else {-1.};
-        let (x_pivot, y_pivot) = formation.data.pivot;
-        let (x_radius, y_radius) = formation.data.radius;
-
-        //compute next angle (based on time for now)
-        let angle = formation.angle + dir * formation.data.speed * TIME_STEP / (x_radius.min(y_radius) * PI / 2.);
+        // where the formation wants this member to be
+        let target = formation.data.path.position(&formation.cursor);
+        let to_target = target - current;
+        let distance = to_target.length();
 
-        //compute target x/y
-        let x_dst = x_radius * angle.cos() + x_pivot;
-        let y_dst = y_radius * angle.sin() + y_pivot;
-
-        //compute distance
-        let dx = x_original - x_dst;
-        let dy = y_original - y_dst;
-        let distance = (dx * dx + dy * dy).sqrt();
-        let distance_ratio = if distance != 0. {
-            max_distance / distance
+        let next = if distance <= max_distance {
+            // on the path: follow it
+            let data = formation.data.clone();
+            let position = data.path.advance(&mut formation.cursor, max_distance, TIME_STEP);
+            if let Some(angle) = data.path.orbit_angle(&formation.cursor) {
+                formation.angle = angle;
+            }
+            position
         } else {
-            0.
+            // off the path (e.g. back from a dive): fly straight back to it
+            current + to_target / distance * max_distance
         };
 
-        //compute final x/y
-        let x = x_original - dx * distance_ratio;
-        let x = if dx > 0. { x.max(x_dst) } else { x.min(x_dst) };
-        let y = y_original - dy * distance_ratio;
-        let y = if dy > 0. { y.max(y_dst) } else { y.min(y_dst) };
+        let translation = &mut transform.translation;
+        (translation.x, translation.y) = (next.x, next.y);
+    }
+}
 
-        // start rotating the formation angle only when sprite is on or close to ellipse
-        if distance < max_distance * formation.data.speed / 20. {
-            formation.angle = angle;
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::event::Events;
+    use crate::enemy::formation::FormationId;
+    use bevy::prelude::Vec2;
 
-        let translation = &mut transform.translation;
-        (translation.x, translation.y) = (x, y);
+    fn app() -> App {
+        let mut app = App::new();
+        app.insert_resource(FormationRegistry::default())
+            .add_event::<Death>()
+            .add_event::<FormationCleared>()
+            .add_system(formation_kill_system)
+            .add_system_to_stage(CoreStage::PostUpdate, formation_removal_system);
+        app
+    }
+
+    /// Spawns the members of a new formation of `members`
+    fn formation(app: &mut App, maker: &mut FormationMaker, members: u32) -> Vec<Entity> {
+        let formation = maker.custom(Vec2::ZERO, Vec2::ZERO, Vec2::ZERO, members, 100., 0);
+        (0..members)
+            .map(|_| {
+                let entity = app.world.spawn().insert(formation.clone()).id();
+                app.world
+                    .resource_mut::<FormationRegistry>()
+                    .spawned(entity, &formation.data);
+                entity
+            })
+            .collect()
+    }
+
+    fn die(app: &mut App, entity: Entity, source: Option<CollisionLayer>) {
+        app.world.despawn(entity);
+        app.world.resource_mut::<Events<Death>>().send(Death {
+            entity,
+            layer: CollisionLayer::Enemy,
+            translation: Vec3::ZERO,
+            source: source.map(|layer| (Entity::from_raw(u32::MAX), layer)),
+        });
+    }
+
+    fn cleared(app: &App) -> Vec<(FormationId, u32, u32)> {
+        let events = app.world.resource::<Events<FormationCleared>>();
+        events
+            .get_reader()
+            .iter(events)
+            .map(|event| (event.id, event.members, event.killed))
+            .collect()
+    }
+
+    #[test]
+    fn last_member_gone_clears_the_formation_once() {
+        let mut app = app();
+        let mut maker = FormationMaker::default();
+        let members = formation(&mut app, &mut maker, 3);
+        let other = formation(&mut app, &mut maker, 1);
+
+        die(&mut app, members[0], Some(CollisionLayer::PlayerLaser));
+        app.update();
+        die(&mut app, members[1], Some(CollisionLayer::PlayerLaser));
+        app.update();
+        assert!(cleared(&app).is_empty());
+
+        die(&mut app, members[2], Some(CollisionLayer::PlayerLaser));
+        app.update();
+        assert_eq!(cleared(&app), vec![(FormationId(0), 3, 3)]);
+
+        // gone for good
+        app.update();
+        app.update();
+        assert!(cleared(&app).is_empty());
+        assert!(app.world.get_entity(other[0]).is_some());
+    }
+
+    #[test]
+    fn only_player_lasers_count_as_killed() {
+        let mut app = app();
+        let mut maker = FormationMaker::default();
+        let members = formation(&mut app, &mut maker, 4);
+
+        die(&mut app, members[0], Some(CollisionLayer::PlayerLaser));
+        // rammed the player
+        die(&mut app, members[1], Some(CollisionLayer::Player));
+        // left the screen at the end of a dive
+        app.world.despawn(members[2]);
+        app.update();
+        app.world.despawn(members[3]);
+        app.update();
+
+        assert_eq!(cleared(&app), vec![(FormationId(0), 4, 1)]);
     }
 }
@@ -1,91 +1,395 @@
 use std::cell::RefCell;
 use std::sync::Arc;
-use bevy::prelude::Component;
-use rand::{Rng, thread_rng};
-use crate::{BASE_SPEED, FORMATION_MEMBERS_MAX, WinSize};
+use bevy::prelude::{Component, Entity, Vec2};
+use bevy::utils::HashMap;
+use rand::Rng;
+use crate::{GameRng, WinSize, TIME_STEP};
+use crate::difficulty::Tuning;
+use crate::enemy::path::{ellipse_perimeter, Path, PathCursor, Segment};
+use serde::{Deserialize, Serialize};
 
 /// Component - Enemy Formation (per enemy)
 #[derive(Component)]
 pub struct Formation {
     pub data: Arc<FormationData>,
-    pub angle: f32 // change per tick
+    pub angle: f32, // orbit angle, change per tick once in the hold pattern
+    pub cursor: PathCursor, // progress along data.path
+    pub slot: u32, // member index within the formation
+    pub delay: f32, // seconds left before this member starts moving
 }
 
 impl Clone for Formation {
     fn clone(&self) -> Self {
         Formation {
             data: self.data.clone(),
-            angle: self.angle
+            angle: self.angle,
+            cursor: self.cursor,
+            slot: self.slot,
+            delay: self.delay,
         }
     }
 }
 
+/// Component - formation of the enemy that fired a laser
+#[derive(Component, Clone)]
+pub struct FiredBy(pub Arc<FormationData>);
+
+/// Identity of a formation, shared by all its members
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct FormationId(pub u32);
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct FormationData {
+    pub id: FormationId,
     pub start: (f32, f32),
     pub radius: (f32, f32),
     pub pivot: (f32, f32),
     pub speed: f32,
+    pub path: Path, // entry path chained into the hold pattern (orbit around pivot)
+    pub members: u32,
+    pub entry_delay: f32, // seconds between two consecutive slots leaving the start
+    pub first_spawn: u64, // game clock tick the first member spawned, slot delays count from it
+}
+
+/// Event - the last member of a formation is gone
+pub struct FormationCleared {
+    pub id: FormationId,
+    pub members: u32,
+    pub killed: u32, // members shot down by the player (the others rammed or left the screen)
 }
 
 /// Resource - Formation Maker
 #[derive(Default)]
 pub struct FormationMaker {
-    current_template: Option<Formation>,
-    current_members: u32,
+    pub(super) current_template: Option<Formation>,
+    pub(super) current_members: u32,
+    pub(super) next_id: u32,
 }
 
 /// Formation factory implementation
 impl FormationMaker {
-    pub fn make(&mut self, win_size: &WinSize) -> Formation {
-        match (&self.current_template, self.current_members >= FORMATION_MEMBERS_MAX) {
-            // if has current template and still within max members
-            (Some(template), false) => {
-                self.current_members += 1;
-                template.clone()
-            }
-            // if first formation or previous formation is full (need to create a new one)
-            (None, _) | (_, true) => {
-                let mut rng = thread_rng();
-
-                // compute the start x/y
-                let w_span = win_size.width / 2. + 100.;
-                let h_span = win_size.height / 2. + 100.;
-                let x = if rng.gen_bool(0.5) { w_span } else { -w_span };
-                let y = rng.gen_range(-h_span..h_span) as f32;
-                let start = (x, y);
-
-                //compute pivot x/y
-                let w_span = win_size.width / 4.;
-                let h_span = win_size.height / 3.;
-                let pivot = (rng.gen_range(-w_span..w_span), rng.gen_range(0.0..h_span));
-
-                //compute the radius
-                let radius = (rng.gen_range(80.0..150.) , 100.);
-
-                //compute the start angle
-                let angle = (y - pivot.1).atan2(x - pivot.0);
-
-                // speed (fixed for now)
-                let speed = BASE_SPEED;
-
-                // create formation
-                let formation = Formation {
-                    data: Arc::new(FormationData {
-                        start,
-                        radius,
-                        pivot,
-                        speed,
-                    }),
-                    angle,
-                };
-
-                //store as template
-                self.current_template = Some(formation.clone());
-                // reset members to 1
-                self.current_members = 1;
-
-                formation
-            }
+    /// Members of the current formation not handed out yet
+    pub fn members_left(&self) -> u32 {
+        match &self.current_template {
+            Some(template) => template.data.members.saturating_sub(self.current_members),
+            None => 0,
         }
     }
-}
\ No newline at end of file
+
+    /// Next member of the current formation, the one of slot `current_members`. Its entry delay
+    /// counts from the spawn of the first member, members spawned late leave straight away.
+    pub fn next_member(&mut self, tick: u64) -> Option<Formation> {
+        if self.members_left() == 0 {
+            return None;
+        }
+        let mut formation = self.current_template.clone()?;
+        let elapsed = tick.saturating_sub(formation.data.first_spawn) as f32 * TIME_STEP;
+        formation.slot = self.current_members;
+        formation.delay = (formation.slot as f32 * formation.data.entry_delay - elapsed).max(0.);
+        self.current_members += 1;
+        Some(formation)
+    }
+
+    /// Starts a new formation of `members` (at least one), its first member spawning at `tick`.
+    /// Returns that first member, the others come from `next_member`.
+    pub fn start(
+        &mut self,
+        members: u32,
+        win_size: &WinSize,
+        tuning: &Tuning,
+        rng: &mut GameRng,
+        tick: u64,
+    ) -> Formation {
+        // compute the start x/y
+        let w_span = win_size.width / 2. + 100.;
+        let h_span = win_size.height / 2. + 100.;
+        let x = if rng.gen_bool(0.5) { w_span } else { -w_span };
+        let y = rng.gen_range(-h_span..h_span);
+        let start = (x, y);
+
+        //compute pivot x/y
+        let w_span = win_size.width / 4.;
+        let h_span = win_size.height / 3.;
+        let pivot = (rng.gen_range(-w_span..w_span), rng.gen_range(0.0..h_span));
+
+        //compute the radius
+        let radius = (rng.gen_range(80.0..150.) , 100.);
+
+        //compute the start angle
+        let angle = (y - pivot.1).atan2(x - pivot.0);
+
+        // speed of the run's difficulty
+        let speed = tuning.enemy_speed();
+
+        // entry path, then hold pattern around the pivot
+        let path = make_path(rng, win_size, start, pivot, radius, angle);
+
+        // members trail each other along the path, evenly spread once on the orbit
+        let members = members.max(1);
+        let perimeter = ellipse_perimeter(Vec2::new(radius.0, radius.1));
+        let entry_delay = perimeter / members as f32 / speed;
+
+        // create formation
+        let formation = Formation {
+            data: Arc::new(FormationData {
+                id: FormationId(self.next_id),
+                start,
+                radius,
+                pivot,
+                speed,
+                path,
+                members,
+                entry_delay,
+                first_spawn: tick,
+            }),
+            angle,
+            cursor: PathCursor::default(),
+            slot: 0,
+            delay: 0.,
+        };
+
+        //store as template
+        self.current_template = Some(formation.clone());
+        self.next_id += 1;
+        // reset members to 1
+        self.current_members = 1;
+
+        formation
+    }
+}
+
+impl FormationMaker {
+    /// A formation made to order (developer console), leaving the game rng and the current
+    /// template alone: flies straight from `start` to an orbit of `radius` around `pivot`,
+    /// or holds still at `start` when `radius` is zero
+    pub fn custom(
+        &mut self,
+        start: Vec2,
+        pivot: Vec2,
+        radius: Vec2,
+        members: u32,
+        speed: f32,
+        tick: u64,
+    ) -> Formation {
+        let angle = (start.y - pivot.y).atan2(start.x - pivot.x);
+        let path = if radius == Vec2::ZERO {
+            Path::new(start)
+        } else {
+            let entry = pivot + radius * Vec2::new(angle.cos(), angle.sin());
+            Path::new(start)
+                .then(Segment::Line { to: entry })
+                .then(Segment::Orbit {
+                    pivot,
+                    radius,
+                    from_angle: angle,
+                    turns: None,
+                    clockwise: start.x > pivot.x,
+                })
+        };
+        let members = members.max(1);
+        let entry_delay = ellipse_perimeter(radius) / members as f32 / speed;
+
+        let data = FormationData {
+            id: FormationId(self.next_id),
+            start: (start.x, start.y),
+            radius: (radius.x, radius.y),
+            pivot: (pivot.x, pivot.y),
+            speed,
+            path,
+            members,
+            entry_delay,
+            first_spawn: tick,
+        };
+        self.next_id += 1;
+
+        Formation {
+            data: Arc::new(data),
+            angle,
+            cursor: PathCursor::default(),
+            slot: 0,
+            delay: 0.,
+        }
+    }
+}
+
+/// Resource - live members of every formation with members still around
+#[derive(Default)]
+pub struct FormationRegistry {
+    members: HashMap<Entity, FormationId>,
+    formations: HashMap<FormationId, FormationStatus>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub(super) struct FormationStatus {
+    members: u32,
+    spawned: u32,
+    alive: u32,
+    killed: u32,
+}
+
+impl FormationRegistry {
+    pub fn spawned(&mut self, entity: Entity, data: &FormationData) {
+        self.members.insert(entity, data.id);
+        let status = self.formations.entry(data.id).or_insert(FormationStatus {
+            members: data.members,
+            spawned: 0,
+            alive: 0,
+            killed: 0,
+        });
+        status.spawned += 1;
+        status.alive += 1;
+    }
+
+    /// The member was shot down by the player (it still has to be `removed`)
+    pub fn killed(&mut self, entity: Entity) {
+        if let Some(status) = self
+            .members
+            .get(&entity)
+            .and_then(|id| self.formations.get_mut(id))
+        {
+            status.killed += 1;
+        }
+    }
+
+    /// The member is gone, for whatever reason. Returns the event to send if it was the last one.
+    pub fn removed(&mut self, entity: Entity) -> Option<FormationCleared> {
+        let id = self.members.remove(&entity)?;
+        let status = self.formations.get_mut(&id)?;
+        status.alive -= 1;
+
+        if status.alive > 0 || status.spawned < status.members {
+            return None;
+        }
+
+        let status = self.formations.remove(&id)?;
+        Some(FormationCleared {
+            id,
+            members: status.members,
+            killed: status.killed,
+        })
+    }
+
+    /// Formations with members still around or to come, by id
+    pub(super) fn statuses(&self) -> Vec<(FormationId, FormationStatus)> {
+        let mut statuses: Vec<_> = self
+            .formations
+            .iter()
+            .map(|(id, status)| (*id, status.clone()))
+            .collect();
+        statuses.sort_by_key(|(id, _)| *id);
+        statuses
+    }
+
+    /// Registry of restored members, with their formations as `statuses` saved them
+    pub(super) fn restored(
+        statuses: Vec<(FormationId, FormationStatus)>,
+        members: impl IntoIterator<Item = (Entity, FormationId)>,
+    ) -> Self {
+        Self {
+            members: members.into_iter().collect(),
+            formations: statuses.into_iter().collect(),
+        }
+    }
+}
+
+/// Random entry path from `start` to the orbit, followed by an endless orbit
+fn make_path(
+    rng: &mut impl Rng,
+    win_size: &WinSize,
+    start: (f32, f32),
+    pivot: (f32, f32),
+    radius: (f32, f32),
+    angle: f32,
+) -> Path {
+    let start = Vec2::new(start.0, start.1);
+    let pivot = Vec2::new(pivot.0, pivot.1);
+    let radius = Vec2::new(radius.0, radius.1);
+
+    // join the orbit where the start point projects on it
+    let entry = pivot + radius * Vec2::new(angle.cos(), angle.sin());
+    let style = rng.gen_range(0..3);
+    let (w_span, h_span) = (win_size.width / 3., win_size.height / 3.);
+    let mut waypoint = || Vec2::new(rng.gen_range(-w_span..w_span), rng.gen_range(-h_span..h_span));
+
+    let path = Path::new(start);
+    let path = match style {
+        // swoop in
+        0 => path.then(Segment::Bezier {
+            control1: waypoint(),
+            control2: waypoint(),
+            to: entry,
+        }),
+        // wander through a few points
+        1 => path.then(Segment::CatmullRom {
+            points: vec![waypoint(), waypoint(), entry],
+        }),
+        // straight in, with a short stop on the way
+        _ => {
+            let halfway = start.lerp(entry, 0.5);
+            path.then(Segment::Line { to: halfway })
+                .then(Segment::Wait { seconds: 0.5 })
+                .then(Segment::Line { to: entry })
+        }
+    };
+
+    // enemies coming from the left turn counter-clockwise, from the right clockwise
+    path.then(Segment::Orbit {
+        pivot,
+        radius,
+        from_angle: angle,
+        turns: None,
+        clockwise: start.x > 0.,
+    })
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::difficulty::Difficulty;
+
+    const WIN_SIZE: WinSize = WinSize {
+        width: 598.,
+        height: 676.,
+    };
+
+    #[test]
+    fn formations_get_the_members_they_start_with() {
+        let tuning = Tuning::new(Difficulty::Normal, false);
+        let mut rng = GameRng::new(1);
+        let mut maker = FormationMaker::default();
+        assert!(maker.next_member(0).is_none());
+
+        for members in [1, 3, 5] {
+            let first = maker.start(members, &WIN_SIZE, &tuning, &mut rng, 0);
+            assert_eq!(first.data.members, members);
+            let slots: Vec<u32> = std::iter::from_fn(|| maker.next_member(0))
+                .map(|member| {
+                    assert_eq!(member.data.id, first.data.id);
+                    member.slot
+                })
+                .collect();
+            assert_eq!(slots, (1..members).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn member_delays_count_from_the_first_spawn() {
+        let tuning = Tuning::new(Difficulty::Normal, false);
+        let mut maker = FormationMaker::default();
+        let first = maker.start(4, &WIN_SIZE, &tuning, &mut GameRng::new(1), 100);
+        let entry_delay = first.data.entry_delay;
+        assert_eq!(first.delay, 0.);
+
+        let second = maker.next_member(100).unwrap();
+        assert_eq!(second.delay, entry_delay);
+
+        // spawned some ticks later, the wait so far counts
+        let ticks = (entry_delay / TIME_STEP) as u64 / 2;
+        let third = maker.next_member(100 + ticks).unwrap();
+        let expected = 2. * entry_delay - ticks as f32 * TIME_STEP;
+        assert!((third.delay - expected).abs() < 1e-4);
+
+        // past its turn, it leaves at once
+        let fourth = maker.next_member(100 + ticks * 10).unwrap();
+        assert_eq!(fourth.delay, 0.);
+    }
+}
@@ -1,4 +1,9 @@
+mod dive;
 mod formation;
+mod path;
 mod enemy;
+mod save;
 
-pub use enemy::EnemyPlugin;
\ No newline at end of file
+pub use enemy::{spawn_custom_formation, spawn_enemy_laser, EnemyPlugin};
+pub use formation::{FiredBy, Formation, FormationCleared, FormationData, FormationId};
+pub use save::EnemiesSave;
@@ -0,0 +1,389 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+/// One piece of a movement `Path`. Every segment starts where the previous one ended.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Segment {
+    /// Straight line
+    Line { to: Vec2 },
+    /// Cubic Bezier curve
+    Bezier {
+        control1: Vec2,
+        control2: Vec2,
+        to: Vec2,
+    },
+    /// Smooth (uniform Catmull-Rom) curve passing through every point, ending on the last one
+    CatmullRom { points: Vec<Vec2> },
+    /// Laps around an ellipse starting at `from_angle`, forever if `turns` is `None`
+    Orbit {
+        pivot: Vec2,
+        radius: Vec2,
+        from_angle: f32,
+        turns: Option<f32>,
+        clockwise: bool,
+    },
+    /// Stand still
+    Wait { seconds: f32 },
+}
+
+/// Chain of segments followed at constant speed. Once the last segment ends, followers
+/// stay on its end point.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(from = "PathSegments", into = "PathSegments")]
+pub struct Path {
+    segments: Vec<Segment>,
+    /// where each segment starts, then where the path ends
+    starts: Vec<Vec2>,
+}
+
+/// What a `Path` is saved as, its segment starts are worked out again on load
+#[derive(Serialize, Deserialize)]
+struct PathSegments {
+    start: Vec2,
+    segments: Vec<Segment>,
+}
+
+/// Where a follower is on a `Path`: segment index and progress through it (0. -> 1.,
+/// or turns done for an `Orbit`)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PathCursor {
+    pub segment: usize,
+    pub t: f32,
+}
+
+const CURVE_SAMPLES: usize = 16;
+
+impl Path {
+    pub fn new(start: Vec2) -> Self {
+        Self {
+            segments: Vec::new(),
+            starts: vec![start],
+        }
+    }
+
+    /// Adds a segment. Nothing can follow an endless one: followers never get past it.
+    pub fn then(mut self, segment: Segment) -> Self {
+        debug_assert!(
+            self.segments
+                .last()
+                .is_none_or(|last| last.span().is_finite()),
+            "segment added after an endless one"
+        );
+        // an endless orbit has no end point, its start stands in for it
+        let t = if segment.span().is_finite() {
+            segment.span()
+        } else {
+            0.
+        };
+        let end = segment.point(self.end(), t);
+        self.segments.push(segment);
+        self.starts.push(end);
+        self
+    }
+
+    pub fn position(&self, cursor: &PathCursor) -> Vec2 {
+        match self.segments.get(cursor.segment) {
+            Some(segment) => segment.point(self.starts[cursor.segment], cursor.t),
+            None => self.end(),
+        }
+    }
+
+    /// Current angle on the ellipse, if the cursor is in an `Orbit`
+    pub fn orbit_angle(&self, cursor: &PathCursor) -> Option<f32> {
+        match self.segments.get(cursor.segment) {
+            Some(segment @ Segment::Orbit { .. }) => Some(segment.angle(cursor.t)),
+            _ => None,
+        }
+    }
+
+    /// Moves the cursor `distance` further along the path (waits consume `delta` seconds instead)
+    /// and returns the new position.
+    pub fn advance(&self, cursor: &mut PathCursor, mut distance: f32, delta: f32) -> Vec2 {
+        while let Some(segment) = self.segments.get(cursor.segment) {
+            let from = self.starts[cursor.segment];
+
+            let step = match segment {
+                Segment::Wait { seconds } => delta / seconds.max(f32::EPSILON),
+                _ => distance / segment.length(from).max(f32::EPSILON),
+            };
+            let left = segment.span() - cursor.t;
+
+            if step < left {
+                cursor.t += step;
+                break;
+            }
+
+            // finish this segment and carry what's left over to the next one
+            match segment {
+                Segment::Wait { .. } => distance = 0.,
+                _ => distance *= 1. - left / step,
+            }
+            cursor.t = 0.;
+            cursor.segment += 1;
+            if distance <= 0. {
+                break;
+            }
+        }
+
+        self.position(cursor)
+    }
+
+    fn end(&self) -> Vec2 {
+        self.starts[self.segments.len()]
+    }
+}
+
+impl From<PathSegments> for Path {
+    fn from(saved: PathSegments) -> Self {
+        saved
+            .segments
+            .into_iter()
+            .fold(Path::new(saved.start), Path::then)
+    }
+}
+
+impl From<Path> for PathSegments {
+    fn from(path: Path) -> Self {
+        Self {
+            start: path.starts[0],
+            segments: path.segments,
+        }
+    }
+}
+
+impl Segment {
+    /// Value of the cursor's `t` at the end of the segment
+    fn span(&self) -> f32 {
+        match self {
+            Segment::Orbit { turns, .. } => turns.unwrap_or(f32::INFINITY),
+            _ => 1.,
+        }
+    }
+
+    fn point(&self, from: Vec2, t: f32) -> Vec2 {
+        match self {
+            Segment::Line { to } => from.lerp(*to, t),
+            Segment::Bezier {
+                control1,
+                control2,
+                to,
+            } => {
+                let u = 1. - t;
+                from * u * u * u
+                    + *control1 * 3. * u * u * t
+                    + *control2 * 3. * u * t * t
+                    + *to * t * t * t
+            }
+            Segment::CatmullRom { points } => catmull_rom(from, points, t),
+            Segment::Orbit { pivot, radius, .. } => {
+                let angle = self.angle(t);
+                *pivot + *radius * Vec2::new(angle.cos(), angle.sin())
+            }
+            Segment::Wait { .. } => from,
+        }
+    }
+
+    fn angle(&self, t: f32) -> f32 {
+        match self {
+            Segment::Orbit {
+                from_angle,
+                clockwise,
+                ..
+            } => {
+                let dir = if *clockwise { -1. } else { 1. };
+                from_angle + dir * t * 2. * PI
+            }
+            _ => 0.,
+        }
+    }
+
+    /// Length of one unit of `t`: the whole segment, or one lap for an `Orbit`
+    fn length(&self, from: Vec2) -> f32 {
+        match self {
+            Segment::Line { to } => from.distance(*to),
+            Segment::Orbit { radius, .. } => ellipse_perimeter(*radius),
+            Segment::Wait { .. } => 0.,
+            _ => (0..CURVE_SAMPLES)
+                .map(|i| {
+                    let t0 = i as f32 / CURVE_SAMPLES as f32;
+                    let t1 = (i + 1) as f32 / CURVE_SAMPLES as f32;
+                    self.point(from, t0).distance(self.point(from, t1))
+                })
+                .sum(),
+        }
+    }
+}
+
+/// Ramanujan's approximation of the perimeter of an ellipse
+pub fn ellipse_perimeter(radius: Vec2) -> f32 {
+    let (a, b) = (radius.x, radius.y);
+    PI * (3. * (a + b) - ((3. * a + b) * (a + 3. * b)).sqrt())
+}
+
+fn catmull_rom(from: Vec2, points: &[Vec2], t: f32) -> Vec2 {
+    if points.is_empty() {
+        return from;
+    }
+
+    // knots: from, points..., with the end points doubled as phantom neighbours
+    let knot = |i: isize| -> Vec2 {
+        let last = points.len() as isize;
+        match i.clamp(0, last) {
+            0 => from,
+            i => points[i as usize - 1],
+        }
+    };
+
+    let spans = points.len() as f32;
+    let scaled = (t.clamp(0., 1.) * spans).min(spans - f32::EPSILON);
+    let span = scaled.floor() as isize;
+    let t = scaled - span as f32;
+
+    let (p0, p1, p2, p3) = (knot(span - 1), knot(span), knot(span + 1), knot(span + 2));
+    let (t2, t3) = (t * t, t * t * t);
+    ((p1 * 2.)
+        + (p2 - p0) * t
+        + (p0 * 2. - p1 * 5. + p2 * 4. - p3) * t2
+        + (p1 * 3. - p0 - p2 * 3. + p3) * t3)
+        * 0.5
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Vec2, b: Vec2) -> bool {
+        a.distance(b) < 1e-3
+    }
+
+    fn at(path: &Path, segment: usize, t: f32) -> Vec2 {
+        path.position(&PathCursor { segment, t })
+    }
+
+    #[test]
+    fn segments_go_from_the_end_of_the_previous_one() {
+        let path = Path::new(Vec2::ZERO)
+            .then(Segment::Line {
+                to: Vec2::new(10., 0.),
+            })
+            .then(Segment::Bezier {
+                control1: Vec2::new(10., 10.),
+                control2: Vec2::new(20., 10.),
+                to: Vec2::new(20., 0.),
+            })
+            .then(Segment::CatmullRom {
+                points: vec![Vec2::new(30., 10.), Vec2::new(40., 0.)],
+            })
+            .then(Segment::Wait { seconds: 1. });
+
+        assert!(close(at(&path, 0, 0.5), Vec2::new(5., 0.)));
+        assert!(close(at(&path, 1, 0.), Vec2::new(10., 0.)));
+        // symmetric controls, halfway is on the axis at 3/4 of their height
+        assert!(close(at(&path, 1, 0.5), Vec2::new(15., 7.5)));
+        // through every point
+        assert!(close(at(&path, 2, 0.), Vec2::new(20., 0.)));
+        assert!(close(at(&path, 2, 0.5), Vec2::new(30., 10.)));
+        assert!(close(at(&path, 2, 1.), Vec2::new(40., 0.)));
+        // waits where the curve ended, and so does the path
+        assert!(close(at(&path, 3, 0.7), Vec2::new(40., 0.)));
+        assert!(close(at(&path, 4, 0.), Vec2::new(40., 0.)));
+    }
+
+    #[test]
+    fn orbits_lap_the_ellipse() {
+        let orbit = |clockwise| {
+            Path::new(Vec2::ZERO).then(Segment::Orbit {
+                pivot: Vec2::new(0., 100.),
+                radius: Vec2::new(20., 10.),
+                from_angle: 0.,
+                turns: Some(1.),
+                clockwise,
+            })
+        };
+        let path = orbit(false);
+        assert!(close(at(&path, 0, 0.), Vec2::new(20., 100.)));
+        assert!(close(at(&path, 0, 0.25), Vec2::new(0., 110.)));
+        assert!(close(at(&orbit(true), 0, 0.25), Vec2::new(0., 90.)));
+        assert!(close(at(&path, 1, 0.), Vec2::new(20., 100.)));
+        let angle = path.orbit_angle(&PathCursor { segment: 0, t: 0.5 });
+        assert!((angle.unwrap() - PI).abs() < 1e-5);
+        assert_eq!(path.orbit_angle(&PathCursor { segment: 1, t: 0. }), None);
+
+        let endless = Path::new(Vec2::ZERO).then(Segment::Orbit {
+            pivot: Vec2::new(0., 100.),
+            radius: Vec2::new(20., 10.),
+            from_angle: 0.,
+            turns: None,
+            clockwise: false,
+        });
+        assert!(close(endless.end(), Vec2::new(20., 100.)));
+    }
+
+    #[test]
+    #[should_panic(expected = "after an endless one")]
+    #[cfg(debug_assertions)]
+    fn nothing_follows_an_endless_orbit() {
+        Path::new(Vec2::ZERO)
+            .then(Segment::Orbit {
+                pivot: Vec2::ZERO,
+                radius: Vec2::ONE,
+                from_angle: 0.,
+                turns: None,
+                clockwise: false,
+            })
+            .then(Segment::Wait { seconds: 1. });
+    }
+
+    #[test]
+    fn advance_carries_over_segment_ends() {
+        let path = Path::new(Vec2::ZERO)
+            .then(Segment::Line {
+                to: Vec2::new(10., 0.),
+            })
+            .then(Segment::Line {
+                to: Vec2::new(10., 20.),
+            })
+            .then(Segment::Wait { seconds: 1. })
+            .then(Segment::Line {
+                to: Vec2::new(0., 20.),
+            });
+        let mut cursor = PathCursor::default();
+
+        let position = path.advance(&mut cursor, 15., 0.1);
+        assert_eq!(cursor.segment, 1);
+        assert!((cursor.t - 0.25).abs() < 1e-5);
+        assert!(close(position, Vec2::new(10., 5.)));
+
+        // the distance left at the wait is dropped, the wait takes seconds
+        let position = path.advance(&mut cursor, 100., 0.1);
+        assert_eq!(cursor.segment, 2);
+        assert!((cursor.t - 0.1).abs() < 1e-5);
+        assert!(close(position, Vec2::new(10., 20.)));
+        path.advance(&mut cursor, 100., 0.4);
+        assert_eq!(cursor.segment, 2);
+        assert!((cursor.t - 0.5).abs() < 1e-5);
+        path.advance(&mut cursor, 100., 0.5);
+        assert_eq!(cursor, PathCursor { segment: 3, t: 0. });
+
+        // and stays on the end
+        let position = path.advance(&mut cursor, 4., 0.1);
+        assert!(close(position, Vec2::new(6., 20.)));
+        let position = path.advance(&mut cursor, 100., 0.1);
+        assert_eq!(cursor, PathCursor { segment: 4, t: 0. });
+        assert!(close(position, Vec2::new(0., 20.)));
+    }
+
+    #[test]
+    fn saved_paths_find_their_segment_starts_again() {
+        let path = Path::new(Vec2::new(1., 2.))
+            .then(Segment::Line {
+                to: Vec2::new(5., 2.),
+            })
+            .then(Segment::Wait { seconds: 0.5 });
+        let text = ron::to_string(&path).unwrap();
+        let loaded: Path = ron::from_str(&text).unwrap();
+        assert_eq!(loaded.starts, path.starts);
+        assert!(close(at(&loaded, 1, 0.3), Vec2::new(5., 2.)));
+    }
+}
@@ -0,0 +1,124 @@
+use crate::enemy::dive::Dive;
+use crate::enemy::enemy::spawn_enemy;
+use crate::enemy::formation::{
+    Formation, FormationData, FormationId, FormationMaker, FormationRegistry, FormationStatus,
+};
+use crate::enemy::path::PathCursor;
+use crate::{Enemy, GameTextures};
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Enemies of a saved run, with the formations they fly in and the state of the formation maker.
+/// Members of a formation share its data again once restored.
+#[derive(Serialize, Deserialize)]
+pub struct EnemiesSave {
+    formations: Vec<FormationData>,
+    statuses: Vec<(FormationId, FormationStatus)>,
+    template: Option<MemberSave>,
+    current_members: u32,
+    next_id: u32,
+    enemies: Vec<EnemySave>,
+}
+
+/// `Formation` component, with its data referred to by id
+#[derive(Serialize, Deserialize)]
+struct MemberSave {
+    formation: FormationId,
+    angle: f32,
+    cursor: PathCursor,
+    slot: u32,
+    delay: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct EnemySave {
+    translation: Vec3,
+    member: MemberSave,
+    dive: Option<Dive>,
+}
+
+impl EnemiesSave {
+    pub fn from_world(world: &mut World) -> Self {
+        let mut formations: HashMap<FormationId, FormationData> = HashMap::default();
+        let mut member = |formation: &Formation| {
+            formations
+                .entry(formation.data.id)
+                .or_insert_with(|| (*formation.data).clone());
+            MemberSave {
+                formation: formation.data.id,
+                angle: formation.angle,
+                cursor: formation.cursor,
+                slot: formation.slot,
+                delay: formation.delay,
+            }
+        };
+
+        let enemies = world
+            .query_filtered::<(&Transform, &Formation, Option<&Dive>), With<Enemy>>()
+            .iter(world)
+            .map(|(transform, formation, dive)| EnemySave {
+                translation: transform.translation,
+                member: member(formation),
+                dive: dive.cloned(),
+            })
+            .collect();
+
+        let maker = world.resource::<FormationMaker>();
+        let template = maker.current_template.as_ref().map(&mut member);
+        let (current_members, next_id) = (maker.current_members, maker.next_id);
+
+        let mut formations: Vec<_> = formations.into_iter().map(|(_, data)| data).collect();
+        formations.sort_by_key(|data| data.id);
+
+        Self {
+            formations,
+            statuses: world.resource::<FormationRegistry>().statuses(),
+            template,
+            current_members,
+            next_id,
+            enemies,
+        }
+    }
+
+    /// Spawns the enemies and restores the formation resources, returns how many enemies there are
+    pub fn restore(self, commands: &mut Commands, textures: &GameTextures) -> u32 {
+        let formations: HashMap<FormationId, Arc<FormationData>> = self
+            .formations
+            .into_iter()
+            .map(|data| (data.id, Arc::new(data)))
+            .collect();
+        let formation = |member: MemberSave| {
+            formations.get(&member.formation).map(|data| Formation {
+                data: data.clone(),
+                angle: member.angle,
+                cursor: member.cursor,
+                slot: member.slot,
+                delay: member.delay,
+            })
+        };
+
+        let mut members = Vec::new();
+        for enemy in self.enemies {
+            let id = enemy.member.formation;
+            if let Some(formation) = formation(enemy.member) {
+                let entity = spawn_enemy(commands, textures, enemy.translation, formation);
+                if let Some(dive) = enemy.dive {
+                    commands.entity(entity).insert(dive);
+                }
+                members.push((entity, id));
+            }
+        }
+        let count = members.len() as u32;
+
+        commands.insert_resource(FormationMaker {
+            current_template: self.template.and_then(formation),
+            current_members: self.current_members,
+            next_id: self.next_id,
+        });
+        commands.insert_resource(FormationRegistry::restored(self.statuses, members));
+
+        count
+    }
+}
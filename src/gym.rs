@@ -0,0 +1,448 @@
+//! The game as a step-able environment, for training agents against it:
+//!
+//! ```no_run
+//! use rust_invaders::gym::{Action, Env, EnvConfig};
+//! # fn act(observation: &[f32]) -> Action { Action::Fire }
+//!
+//! let mut env = Env::new(EnvConfig::default());
+//! let mut observation = env.reset(42);
+//! loop {
+//!     let step = env.step(act(&observation));
+//!     observation = step.observation;
+//!     if step.done {
+//!         break;
+//!     }
+//! }
+//! ```
+//!
+//! Every step is one tick of the simulation, headless and without waiting for real time.
+
+use crate::collision::collider_size;
+use crate::components::{
+    BunkerCell, Enemy, FromEnemy, FromPlayer, Laser, Player, SpriteSize, Velocity,
+};
+use crate::damage::Death;
+use crate::difficulty::Difficulty;
+use crate::settings::Settings;
+use crate::{
+    headless_app, playing, start_run, GameState, PlayerIntent, PlayerState, RunConfig, TickSystem,
+    WinSize, BASE_SPEED, TIME_STEP,
+};
+use bevy::ecs::event::{Events, ManualEventReader};
+use bevy::prelude::*;
+use std::cmp::Ordering;
+
+/// Enemy lasers in the feature vector, the closest to the player first
+pub const FEATURE_LASERS: usize = 4;
+/// Enemies in the feature vector, the closest to the player first
+pub const FEATURE_ENEMIES: usize = 8;
+/// Length of the feature vector: the player, then `(present, dx, dy, vy)` of each laser
+/// and `(present, dx, dy)` of each enemy
+pub const FEATURES: usize = 2 + FEATURE_LASERS * 4 + FEATURE_ENEMIES * 3;
+
+/// Reward of shooting down an enemy
+const KILL_REWARD: f32 = 1.;
+/// Penalty of losing a life
+const DEATH_PENALTY: f32 = 10.;
+
+/// What an agent sees of the field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObservationKind {
+    /// `FEATURES` values: positions relative to the player, in half field sizes
+    Features,
+    /// `width * height` cells of the field, row by row from the top,
+    /// each holding how bright what covers it would be drawn (0 when empty)
+    Raster { width: usize, height: usize },
+}
+
+/// How the environment plays
+#[derive(Debug, Clone, Copy)]
+pub struct EnvConfig {
+    pub difficulty: Difficulty,
+    pub start_wave: u32,
+    pub observation: ObservationKind,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Default for EnvConfig {
+    fn default() -> Self {
+        Self {
+            difficulty: Difficulty::Normal,
+            start_wave: 1,
+            observation: ObservationKind::Features,
+            width: 598.,
+            height: 676.,
+        }
+    }
+}
+
+/// What the agent does for a tick
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Idle,
+    Left,
+    Right,
+    Fire,
+    LeftFire,
+    RightFire,
+}
+
+impl Action {
+    /// Every action, so agents can pick them by index
+    pub const ALL: [Action; 6] = [
+        Action::Idle,
+        Action::Left,
+        Action::Right,
+        Action::Fire,
+        Action::LeftFire,
+        Action::RightFire,
+    ];
+}
+
+impl From<Action> for PlayerIntent {
+    fn from(action: Action) -> Self {
+        use Action::*;
+        PlayerIntent {
+            left: matches!(action, Left | LeftFire),
+            right: matches!(action, Right | RightFire),
+            fire: matches!(action, Fire | LeftFire | RightFire),
+        }
+    }
+}
+
+/// Outcome of a step
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub observation: Vec<f32>,
+    pub reward: f32,
+    /// The run is over, `reset` starts the next one
+    pub done: bool,
+}
+
+/// Resource - the action of the current step, in place of the keyboard
+#[derive(Default)]
+struct EnvAction(PlayerIntent);
+
+/// The simulation, advanced one tick per step
+pub struct Env {
+    app: App,
+    observation: ObservationKind,
+    deaths: ManualEventReader<Death>,
+    lives: u32,
+}
+
+impl Env {
+    pub fn new(config: EnvConfig) -> Self {
+        let settings = Settings {
+            difficulty: config.difficulty,
+            ..default()
+        };
+        let mut app = headless_app(settings, 0, config.start_wave);
+        app.insert_resource(WinSize {
+            width: config.width,
+            height: config.height,
+        })
+        .init_resource::<EnvAction>()
+        .add_system_set_to_stage(
+            CoreStage::PreUpdate,
+            SystemSet::new()
+                .with_run_criteria(playing)
+                .with_system(env_action_system.after(TickSystem::Intent)),
+        );
+
+        Self {
+            app,
+            observation: config.observation,
+            deaths: ManualEventReader::default(),
+            lives: 0,
+        }
+    }
+
+    /// Starts a new run with `seed`, returns what it looks like on its first tick
+    pub fn reset(&mut self, seed: u64) -> Vec<f32> {
+        self.app.world.resource_mut::<RunConfig>().seed = Some(seed);
+        self.app.world.insert_resource(EnvAction::default());
+        start_run(&mut self.app);
+        self.app.update();
+
+        // kills of the previous run are not rewarded
+        let events = self.app.world.resource::<Events<Death>>();
+        self.deaths.iter(events).for_each(drop);
+        self.lives = self.app.world.resource::<PlayerState>().lives;
+        self.observe()
+    }
+
+    /// Plays `action` for a tick
+    pub fn step(&mut self, action: Action) -> Step {
+        self.app.world.insert_resource(EnvAction(action.into()));
+        self.app.update();
+
+        let events = self.app.world.resource::<Events<Death>>();
+        let kills = self
+            .deaths
+            .iter(events)
+            .filter(|death| death.is_kill())
+            .count() as f32;
+        let lives = self.app.world.resource::<PlayerState>().lives;
+        let deaths = self.lives.saturating_sub(lives) as f32;
+        self.lives = lives;
+
+        Step {
+            observation: self.observe(),
+            reward: kills * KILL_REWARD - deaths * DEATH_PENALTY,
+            done: *self.app.world.resource::<GameState>() != GameState::Playing,
+        }
+    }
+
+    /// The current observation, as returned by `reset` and `step`
+    pub fn observe(&mut self) -> Vec<f32> {
+        let field = FieldView::from_world(&mut self.app.world);
+        match self.observation {
+            ObservationKind::Features => field.features(),
+            ObservationKind::Raster { width, height } => field.raster(width, height),
+        }
+    }
+
+    /// The app under the environment, e.g. to read resources or take snapshots
+    pub fn app(&mut self) -> &mut App {
+        &mut self.app
+    }
+}
+
+fn env_action_system(action: Res<EnvAction>, mut intent: ResMut<PlayerIntent>) {
+    *intent = action.0;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Player,
+    Enemy,
+    PlayerLaser,
+    EnemyLaser,
+    Bunker,
+}
+
+impl Kind {
+    /// Brightness in rasters, tells the kinds apart
+    fn brightness(self) -> f32 {
+        match self {
+            Kind::Player => 1.,
+            Kind::Enemy => 0.8,
+            Kind::PlayerLaser => 0.6,
+            Kind::EnemyLaser => 0.4,
+            Kind::Bunker => 0.2,
+        }
+    }
+}
+
+struct Body {
+    kind: Kind,
+    position: Vec2,
+    size: Vec2,
+    velocity: Vec2, // pixels per tick
+}
+
+/// The field, in pixels from its center
+struct FieldView {
+    half_size: Vec2,
+    bodies: Vec<Body>,
+}
+
+impl FieldView {
+    fn from_world(world: &mut World) -> Self {
+        let win_size = world.resource::<WinSize>();
+        let half_size = Vec2::new(win_size.width, win_size.height) / 2.;
+
+        let mut query = world.query_filtered::<(
+            &Transform,
+            &SpriteSize,
+            Option<&Velocity>,
+            Option<&Player>,
+            Option<&Enemy>,
+            Option<&FromPlayer>,
+            Option<&FromEnemy>,
+        ), Or<(With<Player>, With<Enemy>, With<Laser>, With<BunkerCell>)>>(
+        );
+        let bodies = query
+            .iter(world)
+            .map(
+                |(transform, size, velocity, player, enemy, from_player, from_enemy)| {
+                    let kind = match (player, enemy, from_player, from_enemy) {
+                        (Some(_), ..) => Kind::Player,
+                        (_, Some(_), ..) => Kind::Enemy,
+                        (_, _, Some(_), _) => Kind::PlayerLaser,
+                        (_, _, _, Some(_)) => Kind::EnemyLaser,
+                        _ => Kind::Bunker,
+                    };
+                    Body {
+                        kind,
+                        position: transform.translation.truncate(),
+                        size: collider_size(transform, size),
+                        velocity: velocity.map_or(Vec2::ZERO, |velocity| {
+                            Vec2::new(velocity.x, velocity.y) * BASE_SPEED * TIME_STEP
+                        }),
+                    }
+                },
+            )
+            .collect();
+
+        Self { half_size, bodies }
+    }
+
+    fn features(&self) -> Vec<f32> {
+        let player = self.bodies.iter().find(|body| body.kind == Kind::Player);
+        // between two lives, what is relative to the player is relative to where it respawns
+        let origin = player.map_or(Vec2::new(0., -self.half_size.y), |player| player.position);
+        let relative = |body: &Body| (body.position - origin) / self.half_size;
+        let distance = |a: &&Body, b: &&Body| {
+            let (a, b) = (relative(a).length(), relative(b).length());
+            a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+        };
+        let closest = |kind: Kind| {
+            let mut bodies: Vec<&Body> = self
+                .bodies
+                .iter()
+                .filter(|body| body.kind == kind)
+                .collect();
+            bodies.sort_by(distance);
+            bodies
+        };
+
+        let mut features = Vec::with_capacity(FEATURES);
+        features.push(origin.x / self.half_size.x);
+        features.push(if player.is_some() { 1. } else { 0. });
+
+        let lasers = closest(Kind::EnemyLaser);
+        for i in 0..FEATURE_LASERS {
+            match lasers.get(i) {
+                Some(laser) => {
+                    let position = relative(laser);
+                    let vy = laser.velocity.y / self.half_size.y;
+                    features.extend([1., position.x, position.y, vy]);
+                }
+                None => features.extend([0.; 4]),
+            }
+        }
+
+        let enemies = closest(Kind::Enemy);
+        for i in 0..FEATURE_ENEMIES {
+            match enemies.get(i) {
+                Some(enemy) => {
+                    let position = relative(enemy);
+                    features.extend([1., position.x, position.y]);
+                }
+                None => features.extend([0.; 3]),
+            }
+        }
+
+        features
+    }
+
+    /// Draws the bodies as rectangles, brighter kinds over darker ones
+    fn raster(&self, width: usize, height: usize) -> Vec<f32> {
+        let mut cells = vec![0f32; width * height];
+        let cell = self.half_size * 2. / Vec2::new(width as f32, height as f32);
+
+        for body in self.bodies.iter() {
+            // field coordinates grow upward, rows grow downward
+            let min = body.position - body.size / 2. + self.half_size;
+            let max = body.position + body.size / 2. + self.half_size;
+            let columns = (min.x / cell.x).floor().max(0.) as usize
+                ..((max.x / cell.x).ceil().max(0.) as usize).min(width);
+            let rows = (height as f32 - max.y / cell.y).floor().max(0.) as usize
+                ..((height as f32 - min.y / cell.y).ceil().max(0.) as usize).min(height);
+
+            for row in rows {
+                for column in columns.clone() {
+                    let value = &mut cells[row * width + column];
+                    *value = value.max(body.kind.brightness());
+                }
+            }
+        }
+
+        cells
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_episode() {
+        let play = |seed| {
+            let mut env = Env::new(EnvConfig::default());
+            let mut observations = vec![env.reset(seed)];
+            for tick in 0..300 {
+                let action = Action::ALL[tick % Action::ALL.len()];
+                observations.push(env.step(action).observation);
+            }
+            observations
+        };
+        assert_eq!(play(5), play(5));
+    }
+
+    #[test]
+    fn observations_have_a_fixed_size() {
+        let mut env = Env::new(EnvConfig::default());
+        assert_eq!(env.reset(1).len(), FEATURES);
+        assert_eq!(env.step(Action::LeftFire).observation.len(), FEATURES);
+
+        let mut env = Env::new(EnvConfig {
+            observation: ObservationKind::Raster {
+                width: 32,
+                height: 36,
+            },
+            ..default()
+        });
+        env.reset(1);
+        // the player spawns within half a second
+        let observation = (0..30)
+            .map(|_| env.step(Action::Fire).observation)
+            .last()
+            .unwrap();
+        assert_eq!(observation.len(), 32 * 36);
+        assert!(observation
+            .iter()
+            .any(|value| *value == Kind::Player.brightness()));
+    }
+
+    #[test]
+    fn idle_agent_is_penalized_until_done() {
+        let mut env = Env::new(EnvConfig {
+            difficulty: Difficulty::Hard,
+            ..default()
+        });
+        env.reset(3);
+
+        let mut total = 0.;
+        let mut done = false;
+        for _ in 0..20_000 {
+            let step = env.step(Action::Idle);
+            total += step.reward;
+            if step.done {
+                done = true;
+                break;
+            }
+        }
+        assert!(done);
+        assert!(total < 0.);
+    }
+
+    #[test]
+    fn kills_are_rewarded_without_the_score_bonuses() {
+        let mut env = Env::new(EnvConfig::default());
+        env.app().insert_resource(crate::GodMode(true));
+        env.reset(2);
+
+        let mut kills = 0.;
+        for tick in 0..3000 {
+            let reward = env.step(Action::ALL[3 + tick / 40 % 3]).reward;
+            assert_eq!(reward.fract(), 0., "{} is not a number of kills", reward);
+            assert!(reward >= 0.);
+            kills += reward / KILL_REWARD;
+        }
+        assert!(kills > 0.);
+    }
+}
@@ -0,0 +1,779 @@
+#![allow(unused)]
+#![allow(clippy::type_complexity, clippy::module_inception, clippy::too_many_arguments)]
+
+use crate::collision::{first_contacts, CollisionEvent, CollisionLayer, CollisionPlugin, CollisionSystem};
+use crate::damage::{Damage, DamagePlugin, DamageSystem, Death};
+use crate::components::{
+    BunkerCell, Enemy, Explosion, ExplosionToSpawn, FromEnemy, FromPlayer, Laser, MainCamera,
+    Movable, Particle, Player, PreviousTranslation, SpriteSize, Velocity,
+};
+use crate::animation::AnimationPlugin;
+use crate::audio::{BevyAudioBackend, PlaySound, Sound, SoundOutput, SoundPlugin};
+use crate::autopilot::{Autopilot, AutopilotPlugin};
+use crate::bunker::{BunkerLayouts, BunkerPlugin};
+use crate::console::ConsolePlugin;
+use crate::debug::DebugPlugin;
+use crate::difficulty::{Difficulty, DifficultyPlugin};
+use crate::effects::{EffectsConfig, EffectsPlugin};
+use crate::menu::{MenuPlugin, MenuSystem};
+use crate::player::PlayerPlugin;
+use crate::cli::Cli;
+use crate::replay::{Recording, ReplayPlugin};
+use crate::settings::{Settings, SettingsPath};
+use crate::snapshot::SnapshotPlugin;
+use crate::time::{time_scale_system, TimeScale};
+use enemy::{EnemyPlugin, FiredBy, Formation, FormationCleared, FormationData};
+use bevy::app::AppExit;
+use bevy::asset::FileAssetIo;
+use bevy::core::{CoreSystem, DefaultTaskPoolOptions};
+use bevy::ecs::event::Events;
+use bevy::window::WindowMode;
+use clap::Parser;
+use bevy::ecs::schedule::ShouldRun;
+use bevy::prelude::*;
+use rand::{thread_rng, Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+pub mod animation;
+pub mod audio;
+pub mod autopilot;
+pub mod balance;
+mod bunker;
+pub mod cli;
+pub mod collision;
+pub mod components;
+mod console;
+pub mod damage;
+mod debug;
+pub mod difficulty;
+mod effects;
+mod menu;
+mod player;
+mod enemy;
+pub mod gym;
+pub mod replay;
+mod save;
+pub mod snapshot;
+pub mod settings;
+pub mod time;
+
+//region --Asset Constants
+
+const PLAYER_SPRITE: &str = "player_a_01.png";
+const PLAYER_SIZE: (f32, f32) = (144., 75.);
+const PLAYER_LASER_SPRITE: &str = "laser_a_01.png";
+const PLAYER_LASER_SIZE: (f32, f32) = (9., 54.);
+
+const ENEMY_SPRITE: &str = "enemy_a_01.png";
+const ENEMY_SIZE: (f32, f32) = (144., 75.);
+const ENEMY_LASER_SPRITE: &str = "laser_b_01.png";
+const ENEMY_LASER_SIZE: (f32, f32) = (17., 55.);
+
+const EXPLOSION_SHEET: &str = "explo_a_sheet.png";
+const EXPLOSION_LEN: usize = 16;
+const EFFECTS_FILE: &str = "assets/effects.ron";
+const BUNKERS_FILE: &str = "assets/bunkers.ron";
+
+const SPRITE_SCALE: f32 = 0.5;
+
+const HIGH_SCORES_FILE: &str = "high_scores.ron";
+const SAVE_FILE: &str = "save.ron";
+
+//endregion --Asset Constants
+
+//region --Game Constants
+
+const BASE_SPEED: f32 = 500.;
+const TIME_STEP: f32 = 1. / 60.;
+
+// defaults of the Normal difficulty, see `Difficulty::preset` for the others
+const ENEMY_MAX: u32 = 2;
+const ENEMY_FIRE_CHANCE: f64 = 1. / 60.;
+const PLAYER_RESPAWN_DELAY: f64 = 2.;
+const PLAYER_LIVES: u32 = 3;
+const FORMATION_MEMBERS_MAX: u32 = 2;
+const COLLISION_CELL_SIZE: f32 = 64.;
+const ENEMY_DIVE_INTERVAL: f64 = 3.;
+const ENEMY_DIVE_SPEED: f32 = 300.;
+const ENEMY_HEALTH: u32 = 1;
+const PLAYER_HEALTH: u32 = 1;
+
+const ENEMY_POINTS: u32 = 100;
+const FORMATION_BONUS: u32 = 200; // per member, when the whole formation is shot down
+const FORMATIONS_PER_WAVE: u32 = 5;
+
+//endregion --Game Constants
+
+//region --Resources
+
+pub struct WinSize {
+    pub width: f32,
+    pub height: f32,
+}
+
+struct GameTextures {
+    player: Handle<Image>,
+    player_laser: Handle<Image>,
+    enemy: Handle<Image>,
+    enemy_laser: Handle<Image>,
+    explosion: Handle<TextureAtlas>,
+}
+
+/// Resource - enemies alive, recounted every frame once its despawns are applied
+struct EnemyCount(u32);
+
+/// Current wave, starting at 1
+pub struct Wave(pub u32);
+
+/// Formations cleared since the current wave started
+struct WaveProgress(u32);
+
+pub struct Score(pub u32);
+
+/// Resource - which screen the game is on. Gameplay systems only run while `Playing`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GameState {
+    #[default]
+    Title,
+    Settings,
+    HighScores,
+    Playing,
+    Paused,
+    GameOver,
+}
+
+/// Event - clear the field and reset the run, before starting or leaving a game
+pub struct NewGame;
+
+/// What took a life of the player
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DeathCause {
+    Laser,
+    Ram,
+}
+
+/// Event - the player lost a life, to an enemy of `formation` when it is known
+/// (lasers of a restored run forgot who fired them)
+pub struct PlayerKilled {
+    pub cause: DeathCause,
+    pub formation: Option<Arc<FormationData>>,
+}
+
+/// Resource - how new runs start
+pub struct RunConfig {
+    pub seed: Option<u64>, // random for every run if not set
+    pub start_wave: u32,
+}
+
+impl Default for RunConfig {
+    fn default() -> Self {
+        Self {
+            seed: None,
+            start_wave: 1,
+        }
+    }
+}
+
+/// Resource - simulation time of the current run. Advances by one `TIME_STEP` tick
+/// per tick of game time while playing, so a run only depends on its seed and inputs.
+#[derive(Debug, Default)]
+pub struct GameClock {
+    pub tick: u64,
+}
+
+impl GameClock {
+    /// Seconds since the run started
+    pub fn elapsed(&self) -> f64 {
+        self.tick as f64 * TIME_STEP as f64
+    }
+}
+
+/// Resource - enemy lasers and rams go through the player (developer console)
+#[derive(Debug, Default)]
+pub struct GodMode(pub bool);
+
+/// Resource - the only source of randomness of the simulation, reseeded for every run
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GameRng {
+    pub seed: u64,
+    rng: ChaCha8Rng,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
+
+/// Resource - what the player does this tick, from the keyboard or a replay
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlayerIntent {
+    pub left: bool,
+    pub right: bool,
+    pub fire: bool,
+}
+
+/// Label - per tick systems of `PreUpdate`, after the menus changed the game state
+#[derive(Debug, Clone, PartialEq, Eq, Hash, SystemLabel)]
+pub enum TickSystem {
+    Clock,
+    Intent,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct PlayerState {
+    on: bool,
+    last_shot: f64,
+    lives: u32, // left, including the one in play
+}
+
+impl Default for PlayerState {
+    fn default() -> Self {
+        Self {
+            on: false,
+            last_shot: -1.,
+            lives: 0,
+        }
+    }
+}
+
+impl PlayerState {
+    pub fn shot(&mut self, time: f64) {
+        self.on = false;
+        self.last_shot = time;
+        self.lives = self.lives.saturating_sub(1);
+    }
+    /// Out of lives, and the last one had the respawn delay to explode
+    pub fn game_over(&self, time: f64, respawn_delay: f64) -> bool {
+        !self.on && self.lives == 0 && time > self.last_shot + respawn_delay
+    }
+    pub fn spawned(&mut self) {
+        self.on = true;
+        self.last_shot = -1.;
+    }
+}
+//endregion --Resources
+
+/// Query filter - everything a run puts on the field, cleared between runs
+type FieldEntity = Or<(
+    With<Player>,
+    With<Enemy>,
+    With<Laser>,
+    With<Explosion>,
+    With<ExplosionToSpawn>,
+    With<Particle>,
+    With<BunkerCell>,
+)>;
+
+/// Run criteria - gameplay systems, stopped in menus, while paused and between ticks
+fn playing(state: Res<GameState>, time_scale: Res<TimeScale>) -> ShouldRun {
+    if *state == GameState::Playing && time_scale.due {
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
+}
+
+/// Run criteria - chained after another criteria, to also stop it outside of `Playing`
+fn while_playing(
+    In(should_run): In<ShouldRun>,
+    state: Res<GameState>,
+    time_scale: Res<TimeScale>,
+) -> ShouldRun {
+    if *state == GameState::Playing && time_scale.due {
+        should_run
+    } else {
+        ShouldRun::No
+    }
+}
+
+/// Run criteria - once every `seconds` of game time, chain `while_playing` after it
+fn every(seconds: f64) -> impl FnMut(Res<GameClock>) -> ShouldRun {
+    let period = ((seconds / TIME_STEP as f64).round() as u64).max(1);
+    move |clock: Res<GameClock>| {
+        if clock.tick.is_multiple_of(period) {
+            ShouldRun::Yes
+        } else {
+            ShouldRun::No
+        }
+    }
+}
+
+pub fn run() {
+    let mut cli = Cli::parse();
+
+    let mut settings = Settings::load(&cli.config);
+    let mut config = RunConfig {
+        seed: cli.seed,
+        start_wave: cli.wave,
+    };
+    if let Some(difficulty) = cli.difficulty {
+        settings.difficulty = difficulty;
+    }
+    settings.adaptive |= cli.adaptive;
+    settings.audio.muted |= cli.mute;
+
+    // a replay brings the settings of the run it recorded
+    let replay = match cli.replay.as_deref().map(Recording::load).transpose() {
+        Ok(replay) => replay,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(1);
+        }
+    };
+    if let Some(replay) = &replay {
+        config.seed = Some(replay.seed);
+        config.start_wave = replay.start_wave;
+        settings.difficulty = replay.difficulty;
+        settings.adaptive = replay.adaptive;
+        (cli.width, cli.height) = (replay.width, replay.height);
+    }
+
+    let mut app = App::new();
+    settings.insert_into(&mut app);
+    app.insert_resource(config)
+        .insert_resource(Autopilot::new(cli.autopilot))
+        .insert_resource(SettingsPath(cli.config.clone()))
+        .insert_resource(EffectsConfig::load(shipped_file(EFFECTS_FILE)))
+        .insert_resource(BunkerLayouts::load(shipped_file(BUNKERS_FILE)))
+        .insert_resource(ClearColor(Color::rgb(0.04, 0.04, 0.04)));
+
+    if cli.headless {
+        add_headless(
+            &mut app,
+            WinSize {
+                width: cli.width,
+                height: cli.height,
+            },
+        );
+    } else {
+        app.insert_resource(WindowDescriptor {
+            title: "Rust Invaders!".to_string(),
+            width: cli.width,
+            height: cli.height,
+            mode: if cli.fullscreen {
+                WindowMode::BorderlessFullscreen
+            } else {
+                WindowMode::Windowed
+            },
+            ..default()
+        })
+        .add_plugins(DefaultPlugins)
+        .add_plugin(DebugPlugin)
+        .add_plugin(ConsolePlugin)
+        .insert_resource(TimeScale::real_time())
+        .insert_resource(SoundOutput::new(BevyAudioBackend::default()));
+    }
+
+    add_game(&mut app);
+    app.add_plugin(ReplayPlugin {
+        replay,
+        record: cli.record.clone(),
+    });
+    if cli.snapshot.is_some() || cli.checksums.is_some() {
+        app.add_plugin(SnapshotPlugin {
+            snapshot: cli.snapshot.clone(),
+            checksums: cli.checksums.clone(),
+        });
+    }
+
+    // scripted sessions skip the title screen
+    if cli.headless || cli.autopilot || cli.replay.is_some() || cli.record.is_some() {
+        start_run(&mut app);
+    }
+
+    match cli.ticks {
+        Some(ticks) if cli.headless => {
+            for _ in 0..ticks {
+                app.update();
+            }
+            app.world.resource_mut::<Events<AppExit>>().send(AppExit);
+            app.update();
+
+            let clock = app.world.resource::<GameClock>();
+            let player_state = app.world.resource::<PlayerState>();
+            println!(
+                "tick {} score {} wave {} lives {}{}",
+                clock.tick,
+                app.world.resource::<Score>().0,
+                app.world.resource::<Wave>().0,
+                player_state.lives,
+                if *app.world.resource::<GameState>() == GameState::GameOver {
+                    " game over"
+                } else {
+                    ""
+                }
+            );
+        }
+        _ => app.run(),
+    }
+}
+
+/// Where a file shipped next to the assets is, found like the asset server finds them:
+/// in the manifest directory (cargo runs), else the directory of the executable.
+/// Not the working directory, which can be anywhere.
+fn shipped_file(file: &str) -> PathBuf {
+    FileAssetIo::get_root_path().join(file)
+}
+
+/// Everything of the game but the window, input and audio output,
+/// which are up to the caller (e.g. `DefaultPlugins`, or `MinimalPlugins` for headless runs)
+pub fn add_game(app: &mut App) {
+    app.init_resource::<RunConfig>()
+        .init_resource::<GameClock>()
+        .init_resource::<TimeScale>()
+        .init_resource::<GodMode>()
+        .insert_resource(GameRng::new(0))
+        .init_resource::<PlayerIntent>()
+        .init_resource::<GameState>()
+        .add_event::<NewGame>()
+        .add_event::<PlayerKilled>()
+        .add_plugin(SoundPlugin)
+        .add_plugin(MenuPlugin)
+        .add_plugin(DifficultyPlugin)
+        .add_plugin(PlayerPlugin)
+        .add_plugin(EnemyPlugin)
+        .add_plugin(CollisionPlugin)
+        .add_plugin(DamagePlugin)
+        .add_plugin(AnimationPlugin)
+        .add_plugin(EffectsPlugin)
+        .add_plugin(BunkerPlugin)
+        .add_plugin(AutopilotPlugin)
+        .add_startup_system(setup_system)
+        // before the stages whose run criteria read it
+        .add_system_to_stage(CoreStage::First, time_scale_system.after(CoreSystem::Time))
+        .add_system_to_stage(CoreStage::PostUpdate, enemy_count_system)
+        .add_system_to_stage(
+            CoreStage::PreUpdate,
+            new_game_system
+                .after(MenuSystem)
+                .before(TickSystem::Clock),
+        )
+        .add_system_set_to_stage(
+            CoreStage::PreUpdate,
+            SystemSet::new()
+                .with_run_criteria(playing)
+                .with_system(
+                    game_clock_system
+                        .label(TickSystem::Clock)
+                        .after(MenuSystem),
+                ),
+        )
+        .add_system_set(
+            SystemSet::new()
+                .with_run_criteria(playing)
+                .with_system(movable_system.before(CollisionSystem::Broadphase))
+                .with_system(
+                    player_laser_hit_enemy_system
+                        .after(CollisionSystem::Detect)
+                        .before(DamageSystem),
+                )
+                .with_system(
+                    enemy_laser_hit_player_system
+                        .after(CollisionSystem::Detect)
+                        .before(DamageSystem),
+                )
+                .with_system(
+                    enemy_ram_player_system
+                        .after(CollisionSystem::Detect)
+                        .before(DamageSystem),
+                )
+                .with_system(enemy_death_system.after(DamageSystem))
+                .with_system(player_death_system.after(DamageSystem))
+                .with_system(formation_cleared_system),
+        );
+}
+
+/// What `--headless` runs on instead of a window: the simulation, without rendering, input or sound
+pub fn add_headless(app: &mut App, win_size: WinSize) {
+    app.add_plugins(MinimalPlugins)
+        .init_resource::<Input<KeyCode>>()
+        .insert_resource(win_size);
+}
+
+/// The app of a headless run on the default field, as the tests, the gym and the balance
+/// report play it. One thread: a tick is too short to be worth spreading over several.
+pub fn headless_app(settings: Settings, seed: u64, start_wave: u32) -> App {
+    let mut app = App::new();
+    app.insert_resource(DefaultTaskPoolOptions::with_num_threads(1));
+    settings.insert_into(&mut app);
+    app.insert_resource(RunConfig {
+        seed: Some(seed),
+        start_wave,
+    });
+    add_headless(
+        &mut app,
+        WinSize {
+            width: 598.,
+            height: 676.,
+        },
+    );
+    add_game(&mut app);
+    app
+}
+
+/// Skips the title screen: a run starts on the next update
+pub fn start_run(app: &mut App) {
+    app.insert_resource(GameState::Playing);
+    app.world.resource_mut::<Events<NewGame>>().send(NewGame);
+}
+
+/// Works without window and assets too (headless runs), leaving the textures empty
+fn setup_system(
+    mut commands: Commands,
+    texture_atlases: Option<ResMut<Assets<TextureAtlas>>>,
+    asset_server: Option<Res<AssetServer>>,
+    windows: Option<Res<Windows>>,
+) {
+    // add cameras
+    commands
+        .spawn_bundle(OrthographicCameraBundle::new_2d())
+        .insert(MainCamera);
+    commands.spawn_bundle(UiCameraBundle::default());
+
+    // add WinSize resource
+    if let Some(window) = windows.as_ref().and_then(|windows| windows.get_primary()) {
+        commands.insert_resource(WinSize {
+            width: window.width(),
+            height: window.height(),
+        });
+    }
+
+    let load = |path: &str| {
+        asset_server
+            .as_ref()
+            .map_or_else(Handle::default, |asset_server| asset_server.load(path))
+    };
+
+    //create explosion texture atlas
+    let texture_atlas = TextureAtlas::from_grid(load(EXPLOSION_SHEET), Vec2::new(64., 64.), 4, 4);
+    let explosion = texture_atlases.map_or_else(Handle::default, |mut texture_atlases| {
+        texture_atlases.add(texture_atlas)
+    });
+
+    // add GameTextures resource
+    commands.insert_resource(GameTextures {
+        player: load(PLAYER_SPRITE),
+        player_laser: load(PLAYER_LASER_SPRITE),
+        enemy: load(ENEMY_SPRITE),
+        enemy_laser: load(ENEMY_LASER_SPRITE),
+        explosion,
+    });
+    commands.insert_resource(EnemyCount(0));
+    commands.insert_resource(Wave(1));
+    commands.insert_resource(WaveProgress(0));
+    commands.insert_resource(Score(0));
+}
+
+/// Despawns whatever is left of the last run and resets its progress
+fn new_game_system(
+    mut commands: Commands,
+    mut events: EventReader<NewGame>,
+    mut enemy_count: ResMut<EnemyCount>,
+    mut wave: ResMut<Wave>,
+    mut wave_progress: ResMut<WaveProgress>,
+    mut score: ResMut<Score>,
+    mut player_state: ResMut<PlayerState>,
+    mut clock: ResMut<GameClock>,
+    mut rng: ResMut<GameRng>,
+    config: Res<RunConfig>,
+    difficulty: Res<Difficulty>,
+    mut collisions: ResMut<Events<CollisionEvent>>,
+    query: Query<Entity, FieldEntity>,
+) {
+    if events.iter().count() == 0 {
+        return;
+    }
+
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+
+    enemy_count.0 = 0;
+    wave.0 = config.start_wave;
+    wave_progress.0 = 0;
+    score.0 = 0;
+    *player_state = PlayerState {
+        lives: difficulty.preset().lives,
+        ..default()
+    };
+    clock.tick = 0;
+    *rng = GameRng::new(config.seed.unwrap_or_else(|| thread_rng().gen()));
+    // subscribers that were paused must not see contacts of despawned entities
+    collisions.clear();
+}
+
+fn game_clock_system(mut clock: ResMut<GameClock>) {
+    clock.tick += 1;
+}
+
+fn enemy_count_system(mut enemy_count: ResMut<EnemyCount>, query: Query<(), With<Enemy>>) {
+    enemy_count.0 = query.iter().count() as u32;
+}
+
+pub(crate) fn movable_system(
+    mut commands: Commands,
+    win_size: Res<WinSize>,
+    mut query: Query<(Entity, &Velocity, &mut Transform, &Movable)>,
+) {
+    for (entity, velocity, mut transform, movable) in query.iter_mut() {
+        let translation = &mut transform.translation;
+        translation.x += velocity.x * BASE_SPEED * TIME_STEP;
+        translation.y += velocity.y * BASE_SPEED * TIME_STEP;
+
+        if movable.auto_despawn {
+            const MARGIN: f32 = 200.;
+            if translation.y > win_size.height / 2. + MARGIN
+                || translation.y < -win_size.height / 2. - MARGIN
+                || translation.x > win_size.width / 2. + MARGIN
+                || translation.x < -win_size.width / 2. - MARGIN
+            {
+                commands.entity(entity).despawn();
+            }
+        }
+    }
+}
+
+fn player_laser_hit_enemy_system(
+    mut commands: Commands,
+    mut events: EventReader<CollisionEvent>,
+    mut damages: EventWriter<Damage>,
+    mut sounds: EventWriter<PlaySound>,
+) {
+    for event in first_contacts(events.iter()) {
+        if let Some((laser, enemy)) =
+            event.between(CollisionLayer::PlayerLaser, CollisionLayer::Enemy)
+        {
+            damages.send(Damage::hit(enemy, laser));
+            commands.entity(laser).despawn();
+            sounds.send(PlaySound(Sound::Hit));
+        }
+    }
+}
+
+fn enemy_laser_hit_player_system(
+    mut commands: Commands,
+    mut events: EventReader<CollisionEvent>,
+    mut damages: EventWriter<Damage>,
+    mut sounds: EventWriter<PlaySound>,
+    god_mode: Res<GodMode>,
+) {
+    if god_mode.0 {
+        return;
+    }
+
+    for event in first_contacts(events.iter()) {
+        if let Some((laser, player)) =
+            event.between(CollisionLayer::EnemyLaser, CollisionLayer::Player)
+        {
+            damages.send(Damage::hit(player, laser));
+            commands.entity(laser).despawn();
+            sounds.send(PlaySound(Sound::Hit));
+            break;
+        }
+    }
+}
+
+fn enemy_ram_player_system(
+    mut events: EventReader<CollisionEvent>,
+    mut damages: EventWriter<Damage>,
+    mut sounds: EventWriter<PlaySound>,
+    god_mode: Res<GodMode>,
+) {
+    if god_mode.0 {
+        return;
+    }
+
+    for event in first_contacts(events.iter()) {
+        if let Some((player, enemy)) = event.between(CollisionLayer::Player, CollisionLayer::Enemy)
+        {
+            damages.send(Damage::hit(player, enemy));
+            damages.send(Damage::hit(enemy, player));
+            sounds.send(PlaySound(Sound::Hit));
+            break;
+        }
+    }
+}
+
+/// Points for the enemies the player shot down
+fn enemy_death_system(mut score: ResMut<Score>, mut deaths: EventReader<Death>) {
+    for death in deaths.iter() {
+        if death.is_kill() {
+            score.0 += ENEMY_POINTS;
+        }
+    }
+}
+
+fn player_death_system(
+    mut player_state: ResMut<PlayerState>,
+    clock: Res<GameClock>,
+    mut deaths: EventReader<Death>,
+    mut killed: EventWriter<PlayerKilled>,
+    fired_by: Query<&FiredBy>,
+    formations: Query<&Formation>,
+) {
+    for death in deaths.iter() {
+        if death.layer != CollisionLayer::Player {
+            continue;
+        }
+
+        player_state.shot(clock.elapsed());
+        let (cause, formation) = match death.source {
+            Some((enemy, CollisionLayer::Enemy)) => (
+                DeathCause::Ram,
+                formations.get(enemy).ok().map(|formation| formation.data.clone()),
+            ),
+            source => (
+                DeathCause::Laser,
+                source
+                    .and_then(|(laser, _)| fired_by.get(laser).ok())
+                    .map(|fired_by| fired_by.0.clone()),
+            ),
+        };
+        killed.send(PlayerKilled { cause, formation });
+    }
+}
+
+/// Bonus for shooting down whole formations, and next wave every `FORMATIONS_PER_WAVE` cleared
+fn formation_cleared_system(
+    mut score: ResMut<Score>,
+    mut wave: ResMut<Wave>,
+    mut wave_progress: ResMut<WaveProgress>,
+    mut events: EventReader<FormationCleared>,
+) {
+    for event in events.iter() {
+        if event.killed == event.members {
+            score.0 += FORMATION_BONUS * event.members;
+        }
+
+        wave_progress.0 += 1;
+        if wave_progress.0 >= FORMATIONS_PER_WAVE {
+            wave.0 += 1;
+            wave_progress.0 = 0;
+        }
+    }
+}
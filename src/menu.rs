@@ -0,0 +1,519 @@
+use crate::audio::AudioSettings;
+use crate::difficulty::{AdaptiveDifficulty, Difficulty, Tuning};
+use crate::settings::{HighScore, HighScores, KeyBindings, Settings, SettingsPath};
+use crate::save::SaveGame;
+use crate::{GameClock, GameState, NewGame, PlayerState, Score, Wave, HIGH_SCORES_FILE, SAVE_FILE};
+use bevy::app::AppExit;
+use bevy::ecs::event::Events;
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+
+const FONT: &str = "fonts/FiraMono-Medium.ttf";
+const HEADER_SIZE: f32 = 40.;
+const ITEM_SIZE: f32 = 26.;
+const ITEM_COLOR: Color = Color::rgb(0.8, 0.8, 0.8);
+const SELECTED_COLOR: Color = Color::rgb(1., 0.85, 0.2);
+const MESSAGE_COLOR: Color = Color::rgb(1., 0.4, 0.4);
+const VOLUME_STEP: f32 = 0.1;
+
+/// Plugin - title screen, pause overlay, settings and high scores, all keyboard driven
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(MenuState::default())
+            .insert_resource(HighScores::load(HIGH_SCORES_FILE))
+            .init_resource::<SettingsPath>()
+            .add_system_to_stage(CoreStage::PreUpdate, save_load_system.exclusive_system().at_start())
+            .add_system_to_stage(CoreStage::PreUpdate, game_over_system.label(MenuSystem))
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                menu_input_system.label(MenuSystem).after(InputSystem),
+            )
+            .add_system(menu_render_system);
+    }
+}
+
+/// Label - the game state only changes in `PreUpdate`, during these systems,
+/// so a whole `Update` runs in the same state
+#[derive(Debug, Clone, PartialEq, Eq, Hash, SystemLabel)]
+pub struct MenuSystem;
+
+#[derive(Component)]
+struct MenuRoot;
+
+/// Resource - cursor of the open menu
+struct MenuState {
+    selected: usize,
+    /// where the settings screen goes back to
+    settings_return: GameState,
+    /// binding waiting for its new key
+    rebinding: Option<Binding>,
+    /// save or load to do at the start of the next frame, with the whole world at hand
+    request: Option<SaveRequest>,
+    /// outcome of the last request, shown under the header
+    message: Option<String>,
+}
+
+impl Default for MenuState {
+    fn default() -> Self {
+        Self {
+            selected: 0,
+            settings_return: GameState::Title,
+            rebinding: None,
+            request: None,
+            message: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SaveRequest {
+    Save,
+    Load,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Binding {
+    Left,
+    Right,
+    Fire,
+    Pause,
+}
+
+impl Binding {
+    fn key(self, keys: &KeyBindings) -> KeyCode {
+        match self {
+            Binding::Left => keys.left,
+            Binding::Right => keys.right,
+            Binding::Fire => keys.fire,
+            Binding::Pause => keys.pause,
+        }
+    }
+
+    fn key_mut(self, keys: &mut KeyBindings) -> &mut KeyCode {
+        match self {
+            Binding::Left => &mut keys.left,
+            Binding::Right => &mut keys.right,
+            Binding::Fire => &mut keys.fire,
+            Binding::Pause => &mut keys.pause,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MenuItem {
+    Start,
+    Continue,
+    Settings,
+    HighScores,
+    Quit,
+    Resume,
+    Restart,
+    SaveAndQuit,
+    QuitToTitle,
+    Volume,
+    MusicVolume,
+    Muted,
+    Difficulty,
+    Adaptive,
+    Key(Binding),
+    Back,
+}
+
+fn menu_items(state: GameState) -> Vec<MenuItem> {
+    use MenuItem::*;
+    match state {
+        GameState::Title => vec![Start, Continue, Settings, HighScores, Quit],
+        GameState::Paused => vec![Resume, Restart, Settings, SaveAndQuit, QuitToTitle],
+        GameState::GameOver => vec![Restart, QuitToTitle],
+        GameState::Settings => vec![
+            Volume,
+            MusicVolume,
+            Muted,
+            Difficulty,
+            Adaptive,
+            Key(Binding::Left),
+            Key(Binding::Right),
+            Key(Binding::Fire),
+            Key(Binding::Pause),
+            Back,
+        ],
+        GameState::HighScores => vec![Back],
+        GameState::Playing => vec![],
+    }
+}
+
+fn menu_label(
+    item: MenuItem,
+    audio: &AudioSettings,
+    keys: &KeyBindings,
+    difficulty: Difficulty,
+    adaptive: bool,
+    rebinding: Option<Binding>,
+) -> String {
+    let percent = |volume: f32| format!("{:.0}%", volume * 100.);
+    match item {
+        MenuItem::Start => "Start".to_string(),
+        MenuItem::Continue => "Continue".to_string(),
+        MenuItem::Settings => "Settings".to_string(),
+        MenuItem::HighScores => "High Scores".to_string(),
+        MenuItem::Quit => "Quit".to_string(),
+        MenuItem::Resume => "Resume".to_string(),
+        MenuItem::Restart => "Restart".to_string(),
+        MenuItem::SaveAndQuit => "Save and Quit".to_string(),
+        MenuItem::QuitToTitle => "Quit to Title".to_string(),
+        MenuItem::Volume => format!("Volume      < {} >", percent(audio.volume)),
+        MenuItem::MusicVolume => format!("Music       < {} >", percent(audio.music_volume)),
+        MenuItem::Muted => format!("Muted       < {} >", if audio.muted { "yes" } else { "no" }),
+        MenuItem::Difficulty => format!("Difficulty  < {:?} >", difficulty),
+        MenuItem::Adaptive => format!("Adaptive    < {} >", if adaptive { "on" } else { "off" }),
+        MenuItem::Key(binding) => {
+            let key = if rebinding == Some(binding) {
+                "press a key".to_string()
+            } else {
+                format!("{:?}", binding.key(keys))
+            };
+            format!("{:<12}{}", format!("{:?}", binding), key)
+        }
+        MenuItem::Back => "Back".to_string(),
+    }
+}
+
+fn menu_input_system(
+    keyboard: Res<Input<KeyCode>>,
+    mut state: ResMut<GameState>,
+    mut menu: ResMut<MenuState>,
+    mut audio: ResMut<AudioSettings>,
+    mut keys: ResMut<KeyBindings>,
+    mut difficulty: ResMut<Difficulty>,
+    mut adaptive: ResMut<AdaptiveDifficulty>,
+    mut high_scores: ResMut<HighScores>,
+    score: Res<Score>,
+    wave: Res<Wave>,
+    tuning: Res<Tuning>,
+    settings_path: Res<SettingsPath>,
+    mut new_game: EventWriter<NewGame>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if *state == GameState::Playing {
+        if keyboard.just_pressed(keys.pause) {
+            *state = GameState::Paused;
+            menu.selected = 0;
+        }
+        return;
+    }
+
+    // the next key pressed becomes the binding
+    if let Some(binding) = menu.rebinding {
+        if let Some(key) = keyboard.get_just_pressed().next() {
+            *binding.key_mut(&mut keys) = *key;
+            menu.rebinding = None;
+        }
+        return;
+    }
+
+    let items = menu_items(*state);
+    if items.is_empty() {
+        return;
+    }
+
+    if keyboard.just_pressed(KeyCode::Up) {
+        menu.selected = (menu.selected + items.len() - 1) % items.len();
+    }
+    if keyboard.just_pressed(KeyCode::Down) {
+        menu.selected = (menu.selected + 1) % items.len();
+    }
+
+    let mut step = 0;
+    if keyboard.just_pressed(KeyCode::Left) {
+        step -= 1;
+    }
+    if keyboard.just_pressed(KeyCode::Right) {
+        step += 1;
+    }
+
+    let back = keyboard.just_pressed(KeyCode::Escape)
+        || (*state == GameState::Paused && keyboard.just_pressed(keys.pause));
+    let item = if back {
+        match *state {
+            GameState::Title | GameState::GameOver => return,
+            GameState::Paused => MenuItem::Resume,
+            _ => MenuItem::Back,
+        }
+    } else if keyboard.just_pressed(KeyCode::Return) {
+        items[menu.selected.min(items.len() - 1)]
+    } else if step != 0 {
+        // left / right only adjust values
+        let item = items[menu.selected.min(items.len() - 1)];
+        match item {
+            MenuItem::Volume => {
+                audio.volume = (audio.volume + step as f32 * VOLUME_STEP).clamp(0., 1.)
+            }
+            MenuItem::MusicVolume => {
+                audio.music_volume = (audio.music_volume + step as f32 * VOLUME_STEP).clamp(0., 1.)
+            }
+            MenuItem::Muted => audio.muted = !audio.muted,
+            MenuItem::Difficulty => *difficulty = difficulty.cycle(step),
+            MenuItem::Adaptive => adaptive.0 = !adaptive.0,
+            _ => {}
+        }
+        return;
+    } else {
+        return;
+    };
+
+    // runs that ended in a game over are already recorded
+    let end_run = |state: GameState, high_scores: &mut HighScores| {
+        if state == GameState::Paused {
+            record_run(high_scores, &score, &wave, &tuning);
+        }
+    };
+
+    match item {
+        MenuItem::Start => {
+            new_game.send(NewGame);
+            goto(&mut state, &mut menu, GameState::Playing);
+        }
+        MenuItem::Continue => menu.request = Some(SaveRequest::Load),
+        MenuItem::Settings => {
+            menu.settings_return = *state;
+            goto(&mut state, &mut menu, GameState::Settings);
+        }
+        MenuItem::HighScores => goto(&mut state, &mut menu, GameState::HighScores),
+        MenuItem::Quit => exit.send(AppExit),
+        MenuItem::Resume => goto(&mut state, &mut menu, GameState::Playing),
+        MenuItem::Restart => {
+            end_run(*state, &mut high_scores);
+            new_game.send(NewGame);
+            goto(&mut state, &mut menu, GameState::Playing);
+        }
+        // not recorded in the high scores, the run is not over
+        MenuItem::SaveAndQuit => menu.request = Some(SaveRequest::Save),
+        MenuItem::QuitToTitle => {
+            end_run(*state, &mut high_scores);
+            new_game.send(NewGame);
+            goto(&mut state, &mut menu, GameState::Title);
+        }
+        MenuItem::Volume => audio.volume = (audio.volume + VOLUME_STEP).min(1.),
+        MenuItem::MusicVolume => audio.music_volume = (audio.music_volume + VOLUME_STEP).min(1.),
+        MenuItem::Muted => audio.muted = !audio.muted,
+        MenuItem::Difficulty => *difficulty = difficulty.cycle(1),
+        MenuItem::Adaptive => adaptive.0 = !adaptive.0,
+        MenuItem::Key(binding) => menu.rebinding = Some(binding),
+        MenuItem::Back => {
+            let next = match *state {
+                GameState::Settings => {
+                    Settings {
+                        audio: audio.clone(),
+                        keys: keys.clone(),
+                        difficulty: *difficulty,
+                        adaptive: adaptive.0,
+                    }
+                    .save(&settings_path.0);
+                    menu.settings_return
+                }
+                _ => GameState::Title,
+            };
+            goto(&mut state, &mut menu, next);
+        }
+    }
+}
+
+/// Ends the run once the last life is lost
+fn game_over_system(
+    mut state: ResMut<GameState>,
+    mut menu: ResMut<MenuState>,
+    player_state: Res<PlayerState>,
+    mut high_scores: ResMut<HighScores>,
+    score: Res<Score>,
+    wave: Res<Wave>,
+    tuning: Res<Tuning>,
+    clock: Res<GameClock>,
+) {
+    if *state == GameState::Playing
+        && player_state.game_over(clock.elapsed(), tuning.respawn_delay())
+    {
+        record_run(&mut high_scores, &score, &wave, &tuning);
+        goto(&mut state, &mut menu, GameState::GameOver);
+    }
+}
+
+fn record_run(high_scores: &mut HighScores, score: &Score, wave: &Wave, tuning: &Tuning) {
+    let entry = HighScore {
+        score: score.0,
+        wave: wave.0,
+        difficulty: tuning.difficulty,
+        adaptive: tuning.adaptive,
+    };
+    if high_scores.record(entry) {
+        high_scores.save(HIGH_SCORES_FILE);
+    }
+}
+
+fn goto(state: &mut GameState, menu: &mut MenuState, next: GameState) {
+    *state = next;
+    menu.selected = 0;
+    menu.message = None;
+}
+
+/// Saves the paused run and quits to the title, or resumes the saved run from the title.
+/// Failures leave the game where it was, with the error on screen.
+fn save_load_system(world: &mut World) {
+    // only touched mutably when there is a request, not to rebuild the menu every frame
+    let request = match world.resource::<MenuState>().request {
+        Some(request) => request,
+        None => return,
+    };
+    world.resource_mut::<MenuState>().request = None;
+
+    let next = match request {
+        SaveRequest::Save => match SaveGame::from_world(world).save(SAVE_FILE) {
+            Ok(()) => {
+                world.resource_mut::<Events<NewGame>>().send(NewGame);
+                GameState::Title
+            }
+            Err(err) => return failed(world, err),
+        },
+        SaveRequest::Load => match SaveGame::load(SAVE_FILE) {
+            Ok(save) => {
+                save.restore(world);
+                GameState::Playing
+            }
+            Err(err) => return failed(world, err),
+        },
+    };
+
+    world.resource_scope(|world, mut menu: Mut<MenuState>| {
+        goto(&mut world.resource_mut::<GameState>(), &mut menu, next);
+    });
+
+    fn failed(world: &mut World, err: String) {
+        warn!("{}", err);
+        world.resource_mut::<MenuState>().message = Some(err);
+    }
+}
+
+/// Rebuilds the menu's UI whenever anything it shows changes
+fn menu_render_system(
+    mut commands: Commands,
+    asset_server: Option<Res<AssetServer>>,
+    state: Res<GameState>,
+    menu: Res<MenuState>,
+    audio: Res<AudioSettings>,
+    keys: Res<KeyBindings>,
+    difficulty: Res<Difficulty>,
+    adaptive: Res<AdaptiveDifficulty>,
+    high_scores: Res<HighScores>,
+    score: Res<Score>,
+    query: Query<Entity, With<MenuRoot>>,
+) {
+    // nothing to show in headless runs
+    let asset_server = match asset_server {
+        Some(asset_server) => asset_server,
+        None => return,
+    };
+
+    let changed = state.is_changed()
+        || menu.is_changed()
+        || audio.is_changed()
+        || keys.is_changed()
+        || difficulty.is_changed()
+        || adaptive.is_changed()
+        || high_scores.is_changed();
+    if !changed {
+        return;
+    }
+
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let (header, background) = match *state {
+        GameState::Playing => return,
+        GameState::Title => ("RUST INVADERS".to_string(), Color::NONE),
+        GameState::Paused => (
+            format!("PAUSED - {}", score.0),
+            Color::rgba(0., 0., 0., 0.6),
+        ),
+        GameState::GameOver => (
+            format!("GAME OVER - {}", score.0),
+            Color::rgba(0., 0., 0., 0.6),
+        ),
+        GameState::Settings => ("SETTINGS".to_string(), Color::rgba(0., 0., 0., 0.8)),
+        GameState::HighScores => ("HIGH SCORES".to_string(), Color::NONE),
+    };
+
+    let font = asset_server.load(FONT);
+    let text = |value: String, size: f32, color: Color| TextBundle {
+        style: Style {
+            margin: Rect::all(Val::Px(6.)),
+            ..default()
+        },
+        text: Text::with_section(
+            value,
+            TextStyle {
+                font: font.clone(),
+                font_size: size,
+                color,
+            },
+            default(),
+        ),
+        ..default()
+    };
+
+    let mut lines = vec![text(header, HEADER_SIZE, Color::WHITE)];
+    if let Some(message) = &menu.message {
+        lines.push(text(message.clone(), ITEM_SIZE, MESSAGE_COLOR));
+    }
+
+    if *state == GameState::HighScores {
+        if high_scores.entries.is_empty() {
+            lines.push(text("no scores yet".to_string(), ITEM_SIZE, ITEM_COLOR));
+        }
+        for (rank, entry) in high_scores.entries.iter().enumerate() {
+            let line = format!(
+                "{:>2}. {:>7}  wave {:<3} {:?}{}",
+                rank + 1,
+                entry.score,
+                entry.wave,
+                entry.difficulty,
+                if entry.adaptive { "*" } else { "" }
+            );
+            lines.push(text(line, ITEM_SIZE, ITEM_COLOR));
+        }
+        if high_scores.entries.iter().any(|entry| entry.adaptive) {
+            lines.push(text("* adaptive".to_string(), ITEM_SIZE, ITEM_COLOR));
+        }
+    }
+
+    for (i, item) in menu_items(*state).into_iter().enumerate() {
+        let label = menu_label(item, &audio, &keys, *difficulty, adaptive.0, menu.rebinding);
+        let line = if i == menu.selected {
+            text(format!("> {} <", label), ITEM_SIZE, SELECTED_COLOR)
+        } else {
+            text(label, ITEM_SIZE, ITEM_COLOR)
+        };
+        lines.push(line);
+    }
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                // top to bottom
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: background.into(),
+            ..default()
+        })
+        .insert(MenuRoot)
+        .with_children(|parent| {
+            for line in lines {
+                parent.spawn_bundle(line);
+            }
+        });
+}
@@ -1,7 +1,14 @@
-use crate::components::{FromPlayer, Movable, Player, SpriteSize, Velocity};
-use crate::{GameTextures, Laser, WinSize, BASE_SPEED, PLAYER_LASER_SIZE, PLAYER_SIZE, SPRITE_SCALE, PlayerState, PLAYER_RESPAWN_DELAY};
-use bevy::core::FixedTimestep;
+use crate::audio::{PlaySound, Sound};
+use crate::collision::CollisionLayer;
+use crate::components::{
+    FromPlayer, Movable, Player, PreviousTranslation, SpriteSize, Velocity,
+};
+use crate::damage::Health;
+use crate::difficulty::Tuning;
+use crate::settings::KeyBindings;
+use crate::{every, movable_system, playing, while_playing, GameClock, GameTextures, PlayerIntent, TickSystem, Laser, WinSize, BASE_SPEED, PLAYER_HEALTH, PLAYER_LASER_SIZE, PLAYER_SIZE, SPRITE_SCALE, PlayerState};
 use bevy::ecs::query;
+use bevy::input::InputSystem;
 use bevy::prelude::*;
 
 pub struct PlayerPlugin;
@@ -11,57 +18,103 @@ impl Plugin for PlayerPlugin {
         app.insert_resource(PlayerState::default())
             .add_system_set(
             SystemSet::new()
-                .with_run_criteria(FixedTimestep::step(0.5))
+                .with_run_criteria(every(0.5).chain(while_playing))
                 .with_system(player_spawn_system),
         )
-        .add_system(player_keyboard_event_system)
-        .add_system(player_fire_system);
+        .add_system_set_to_stage(
+            CoreStage::PreUpdate,
+            SystemSet::new().with_run_criteria(playing).with_system(
+                player_intent_system
+                    .label(TickSystem::Intent)
+                    .after(TickSystem::Clock)
+                    .after(InputSystem),
+            ),
+        )
+        .add_system_set(
+            SystemSet::new()
+                .with_run_criteria(playing)
+                // intents apply to this tick's move, lasers leave from where the player was
+                .with_system(player_move_system.before(movable_system))
+                .with_system(player_fire_system.before(movable_system)),
+        );
     }
 }
 
 fn player_spawn_system(
     mut commands: Commands,
     mut player_state: ResMut<PlayerState>,
-    time: Res<Time>,
+    tuning: Res<Tuning>,
+    clock: Res<GameClock>,
     game_textures: Res<GameTextures>,
     win_size: Res<WinSize>,
+    mut sounds: EventWriter<PlaySound>,
 ) {
-    let now = time.seconds_since_startup();
+    let now = clock.elapsed();
     let last_shot = player_state.last_shot;
 
-    if player_state.on || !(last_shot == -1. || now > last_shot + PLAYER_RESPAWN_DELAY) {
+    if player_state.on
+        || player_state.lives == 0
+        || !(last_shot == -1. || now > last_shot + tuning.respawn_delay())
+    {
         return;
     }
 
     let bottom = -win_size.height / 2.;
+    let translation = Vec3::new(0., bottom + PLAYER_SIZE.1 / 2. * SPRITE_SCALE + 5., 10.);
+    spawn_player(&mut commands, &game_textures, translation);
+
+    player_state.spawned();
+    sounds.send(PlaySound(Sound::Respawn));
+}
+
+pub fn spawn_player(
+    commands: &mut Commands,
+    game_textures: &GameTextures,
+    translation: Vec3,
+) -> Entity {
     commands
         .spawn_bundle(SpriteBundle {
             texture: game_textures.player.clone(),
             transform: Transform {
-                translation: Vec3::new(0., bottom + PLAYER_SIZE.1 / 2. * SPRITE_SCALE + 5., 10.),
+                translation,
                 scale: Vec3::new(SPRITE_SCALE, SPRITE_SCALE, 1.),
                 ..default()
             },
             ..default()
         })
         .insert(Player)
+        .insert(CollisionLayer::Player)
+        .insert(PreviousTranslation(translation))
         .insert(SpriteSize::from(PLAYER_SIZE))
         .insert(Movable {
             auto_despawn: false,
         })
-        .insert(Velocity { x: 0., y: 0. });
-
-    player_state.spawned();
+        .insert(Velocity { x: 0., y: 0. })
+        .insert(Health(PLAYER_HEALTH))
+        .id()
 }
 
-fn player_keyboard_event_system(
+/// Keyboard to intent, replays and bots write the intent after this
+fn player_intent_system(
     keyboard: Res<Input<KeyCode>>,
+    keys: Res<KeyBindings>,
+    mut intent: ResMut<PlayerIntent>,
+) {
+    *intent = PlayerIntent {
+        left: keyboard.pressed(keys.left),
+        right: keyboard.pressed(keys.right),
+        fire: keyboard.just_pressed(keys.fire),
+    };
+}
+
+fn player_move_system(
+    intent: Res<PlayerIntent>,
     mut query: Query<&mut Velocity, With<Player>>,
 ) {
     if let Ok(mut velocity) = query.get_single_mut() {
-        velocity.x = if keyboard.pressed(KeyCode::Left) {
+        velocity.x = if intent.left {
             -1.
-        } else if keyboard.pressed(KeyCode::Right) {
+        } else if intent.right {
             1.
         } else {
             0.
@@ -71,38 +124,49 @@ fn player_keyboard_event_system(
 
 fn player_fire_system(
     mut commands: Commands,
-    keyboard: Res<Input<KeyCode>>,
+    intent: Res<PlayerIntent>,
     game_textures: Res<GameTextures>,
     query: Query<&Transform, With<Player>>,
+    mut sounds: EventWriter<PlaySound>,
 ) {
     if let Ok(player_transform) = query.get_single() {
-        if keyboard.just_pressed(KeyCode::Space) {
+        if intent.fire {
             let (x, y) = (
                 player_transform.translation.x,
                 player_transform.translation.y,
             );
             let x_offset = PLAYER_SIZE.0 / 2. * SPRITE_SCALE - 5.;
 
-            let mut spawn_laser = |x_offset: f32| {
-                commands
-                    .spawn_bundle(SpriteBundle {
-                        texture: game_textures.player_laser.clone(),
-                        transform: Transform {
-                            translation: Vec3::new(x + x_offset, y + 15., 0.),
-                            scale: Vec3::new(SPRITE_SCALE, SPRITE_SCALE, 1.),
-                            ..default()
-                        },
-                        ..default()
-                    })
-                    .insert(Laser)
-                    .insert(FromPlayer)
-                    .insert(SpriteSize::from(PLAYER_LASER_SIZE))
-                    .insert(Movable { auto_despawn: true })
-                    .insert(Velocity { x: 0., y: 1. });
-            };
-
-            spawn_laser(x_offset);
-            spawn_laser(-x_offset);
+            for x_offset in [x_offset, -x_offset] {
+                let translation = Vec3::new(x + x_offset, y + 15., 0.);
+                spawn_player_laser(&mut commands, &game_textures, translation);
+            }
+            sounds.send(PlaySound(Sound::PlayerLaser));
         }
     }
 }
+
+pub fn spawn_player_laser(
+    commands: &mut Commands,
+    game_textures: &GameTextures,
+    translation: Vec3,
+) -> Entity {
+    commands
+        .spawn_bundle(SpriteBundle {
+            texture: game_textures.player_laser.clone(),
+            transform: Transform {
+                translation,
+                scale: Vec3::new(SPRITE_SCALE, SPRITE_SCALE, 1.),
+                ..default()
+            },
+            ..default()
+        })
+        .insert(Laser)
+        .insert(FromPlayer)
+        .insert(CollisionLayer::PlayerLaser)
+        .insert(PreviousTranslation(translation))
+        .insert(SpriteSize::from(PLAYER_LASER_SIZE))
+        .insert(Movable { auto_despawn: true })
+        .insert(Velocity { x: 0., y: 1. })
+        .id()
+}
@@ -0,0 +1,315 @@
+use crate::autopilot::AutopilotSystem;
+use crate::difficulty::{Difficulty, Tuning};
+use crate::{playing, GameClock, GameRng, PlayerIntent, RunConfig, TickSystem, WinSize};
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Bumped whenever a change to the simulation makes old recordings play differently
+pub const RECORDING_VERSION: u32 = 5;
+
+const LEFT: u8 = 1;
+const RIGHT: u8 = 2;
+const FIRE: u8 = 4;
+
+/// Plugin - plays back a recorded run and / or records the current one
+pub struct ReplayPlugin {
+    pub replay: Option<Recording>,
+    pub record: Option<PathBuf>,
+}
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        if let Some(replay) = &self.replay {
+            app.insert_resource(Replay(replay.clone()))
+                // over the size of the window, formations are laid out from it
+                .add_startup_system_to_stage(StartupStage::PostStartup, replay_field_system)
+                .add_system_set_to_stage(
+                    CoreStage::PreUpdate,
+                    SystemSet::new().with_run_criteria(playing).with_system(
+                        replay_intent_system
+                            .label(ReplaySystem)
+                            .after(TickSystem::Intent),
+                    ),
+                );
+        }
+
+        if let Some(path) = &self.record {
+            app.insert_resource(Recorder {
+                path: path.clone(),
+                intents: Vec::new(),
+            })
+            .add_system_set_to_stage(
+                CoreStage::PreUpdate,
+                SystemSet::new().with_run_criteria(playing).with_system(
+                    record_intent_system
+                        .after(TickSystem::Intent)
+                        .after(ReplaySystem)
+                        .after(AutopilotSystem),
+                ),
+            )
+            .add_system_to_stage(CoreStage::Last, record_save_system);
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, SystemLabel)]
+struct ReplaySystem;
+
+/// Inputs of a run, with everything else it takes to play it again
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recording {
+    pub version: u32,
+    pub seed: u64,
+    pub difficulty: Difficulty,
+    pub adaptive: bool,
+    pub start_wave: u32,
+    /// size of the field the run was played on
+    pub width: f32,
+    pub height: f32,
+    /// one `PlayerIntent` per tick, as bits
+    pub intents: Vec<u8>,
+}
+
+impl Recording {
+    /// Recording of a scripted run, one intent per tick
+    pub fn new(
+        seed: u64,
+        difficulty: Difficulty,
+        adaptive: bool,
+        start_wave: u32,
+        win_size: &WinSize,
+        intents: impl IntoIterator<Item = PlayerIntent>,
+    ) -> Self {
+        Self {
+            version: RECORDING_VERSION,
+            seed,
+            difficulty,
+            adaptive,
+            start_wave,
+            width: win_size.width,
+            height: win_size.height,
+            intents: intents
+                .into_iter()
+                .map(|intent| intent_bits(&intent))
+                .collect(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|err| format!("could not read {}: {}", path.display(), err))?;
+        let recording: Recording = ron::from_str(&text)
+            .map_err(|err| format!("{} is not a recording: {}", path.display(), err))?;
+        if recording.version != RECORDING_VERSION {
+            return Err(format!(
+                "{} was recorded by version {} of the game, this is version {}",
+                path.display(),
+                recording.version,
+                RECORDING_VERSION
+            ));
+        }
+        Ok(recording)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let text = ron::to_string(self).map_err(|err| err.to_string())?;
+        fs::write(path, text).map_err(|err| format!("could not write {}: {}", path.display(), err))
+    }
+
+    /// Intent of a tick of the run (ticks start at 1), idle once the recording ran out
+    pub fn intent(&self, tick: u64) -> PlayerIntent {
+        let bits = (tick as usize)
+            .checked_sub(1)
+            .and_then(|index| self.intents.get(index))
+            .copied()
+            .unwrap_or(0);
+        PlayerIntent {
+            left: bits & LEFT != 0,
+            right: bits & RIGHT != 0,
+            fire: bits & FIRE != 0,
+        }
+    }
+}
+
+fn intent_bits(intent: &PlayerIntent) -> u8 {
+    let mut bits = 0;
+    if intent.left {
+        bits |= LEFT;
+    }
+    if intent.right {
+        bits |= RIGHT;
+    }
+    if intent.fire {
+        bits |= FIRE;
+    }
+    bits
+}
+
+/// Resource - the run being played back
+pub struct Replay(pub Recording);
+
+/// Resource - inputs of the current run so far, saved to `path` when the app exits
+pub struct Recorder {
+    pub path: PathBuf,
+    pub intents: Vec<u8>,
+}
+
+fn replay_field_system(mut commands: Commands, replay: Res<Replay>) {
+    commands.insert_resource(WinSize {
+        width: replay.0.width,
+        height: replay.0.height,
+    });
+}
+
+fn replay_intent_system(
+    replay: Res<Replay>,
+    clock: Res<GameClock>,
+    mut intent: ResMut<PlayerIntent>,
+) {
+    *intent = replay.0.intent(clock.tick);
+}
+
+fn record_intent_system(
+    mut recorder: ResMut<Recorder>,
+    clock: Res<GameClock>,
+    intent: Res<PlayerIntent>,
+) {
+    // a new run started, only the last one is kept
+    if clock.tick == 1 {
+        recorder.intents.clear();
+    }
+    recorder.intents.push(intent_bits(&intent));
+}
+
+fn record_save_system(
+    recorder: Res<Recorder>,
+    rng: Res<GameRng>,
+    tuning: Res<Tuning>,
+    config: Res<RunConfig>,
+    win_size: Res<WinSize>,
+    mut exit: EventReader<AppExit>,
+) {
+    if exit.iter().count() == 0 {
+        return;
+    }
+
+    let recording = Recording {
+        version: RECORDING_VERSION,
+        seed: rng.seed,
+        difficulty: tuning.difficulty,
+        adaptive: tuning.adaptive,
+        start_wave: config.start_wave,
+        width: win_size.width,
+        height: win_size.height,
+        intents: recorder.intents.clone(),
+    };
+    match recording.save(&recorder.path) {
+        Ok(()) => info!(
+            "recorded {} ticks to {}",
+            recording.intents.len(),
+            recorder.path.display()
+        ),
+        Err(err) => error!("{}", err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::Cli;
+    use crate::settings::Settings;
+    use crate::snapshot::{Checksums, SnapshotPlugin};
+    use crate::{add_game, add_headless, headless_app, start_run};
+    use clap::Parser;
+
+    fn recording(width: f32, height: f32, ticks: u64) -> Recording {
+        let script = |tick: u64| PlayerIntent {
+            left: tick % 90 < 30,
+            right: tick % 90 >= 60,
+            fire: tick.is_multiple_of(20),
+        };
+        Recording::new(
+            7,
+            Difficulty::Normal,
+            false,
+            1,
+            &WinSize { width, height },
+            (1..=ticks).map(script),
+        )
+    }
+
+    #[test]
+    fn recordings_keep_their_field_size() {
+        let path = std::env::temp_dir().join("rust_invaders_field_size.ron");
+        recording(800., 500., 10).save(&path).unwrap();
+        let loaded = Recording::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!((loaded.width, loaded.height), (800., 500.));
+        assert_eq!(loaded.intent(10), recording(800., 500., 10).intent(10));
+    }
+
+    #[test]
+    fn replays_play_on_the_recorded_field() {
+        let mut app = headless_app(Settings::default(), 7, 1);
+        app.add_plugin(ReplayPlugin {
+            replay: Some(recording(800., 500., 10)),
+            record: None,
+        });
+        start_run(&mut app);
+        app.update();
+
+        let win_size = app.world.resource::<WinSize>();
+        assert_eq!((win_size.width, win_size.height), (800., 500.));
+    }
+
+    /// Systems that share the game rng or the same entities are ordered, so spreading the
+    /// tick over more threads does not change what gets played
+    #[test]
+    fn replays_play_the_same_on_any_number_of_threads() {
+        let played = |mut app: App| {
+            app.add_plugin(ReplayPlugin {
+                replay: Some(recording(598., 676., 600)),
+                record: None,
+            })
+            .add_plugin(SnapshotPlugin::default());
+            start_run(&mut app);
+            for _ in 0..600 {
+                app.update();
+            }
+            app.world.resource::<Checksums>().clone()
+        };
+
+        let mut threaded = App::new();
+        Settings::default().insert_into(&mut threaded);
+        threaded.insert_resource(RunConfig {
+            seed: Some(7),
+            start_wave: 1,
+        });
+        add_headless(
+            &mut threaded,
+            WinSize {
+                width: 598.,
+                height: 676.,
+            },
+        );
+        add_game(&mut threaded);
+
+        let expected = played(headless_app(Settings::default(), 7, 1));
+        assert_eq!(expected.0.len(), 600);
+        assert_eq!(played(threaded), expected);
+    }
+
+    #[test]
+    fn replays_bring_their_own_window_size() {
+        for option in ["--width", "--height"] {
+            let args = ["rust-invaders", "--replay", "run.ron", option, "800"];
+            assert!(Cli::try_parse_from(args).is_err());
+        }
+        let args = ["rust-invaders", "--replay", "run.ron", "--fullscreen"];
+        assert!(Cli::try_parse_from(args).is_err());
+        assert!(Cli::try_parse_from(["rust-invaders", "--replay", "run.ron"]).is_ok());
+    }
+}
@@ -0,0 +1,274 @@
+use crate::bunker::{spawn_bunker_cell, BunkersBuilt};
+use crate::collision::CollisionEvent;
+use crate::components::{BunkerCell, FromPlayer, Laser, Player, Velocity};
+use crate::difficulty::Tuning;
+use crate::enemy::{spawn_enemy_laser, EnemiesSave};
+use crate::player::{spawn_player, spawn_player_laser};
+use crate::{
+    EnemyCount, FieldEntity, GameClock, GameRng, GameTextures, PlayerIntent, PlayerState, Score,
+    Wave, WaveProgress,
+};
+use bevy::ecs::event::Events;
+use bevy::ecs::system::CommandQueue;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Bumped whenever `SaveGame` changes, older saves are refused
+pub const SAVE_VERSION: u32 = 2;
+
+/// A run in progress, restored exactly as it was saved
+#[derive(Serialize, Deserialize)]
+pub struct SaveGame {
+    version: u32,
+    score: u32,
+    wave: u32,
+    wave_progress: u32,
+    tick: u64,
+    rng: GameRng,
+    tuning: Tuning,
+    player: PlayerSave,
+    enemies: EnemiesSave,
+    lasers: Vec<LaserSave>,
+    bunker_cells: Vec<BunkerCellSave>,
+}
+
+/// Read first, so a save of another version fails on its version rather than its content
+#[derive(Deserialize)]
+#[serde(rename = "SaveGame")]
+struct SaveHeader {
+    version: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PlayerSave {
+    state: PlayerState,
+    translation: Option<Vec3>, // none between two lives
+}
+
+#[derive(Serialize, Deserialize)]
+struct LaserSave {
+    translation: Vec3,
+    velocity: Vec2,
+    from_player: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BunkerCellSave {
+    translation: Vec3,
+    health: u8,
+}
+
+impl SaveGame {
+    pub fn from_world(world: &mut World) -> Self {
+        let translation = world
+            .query_filtered::<&Transform, With<Player>>()
+            .iter(world)
+            .next()
+            .map(|transform| transform.translation);
+
+        let lasers = world
+            .query_filtered::<(&Transform, &Velocity, Option<&FromPlayer>), With<Laser>>()
+            .iter(world)
+            .map(|(transform, velocity, from_player)| LaserSave {
+                translation: transform.translation,
+                velocity: Vec2::new(velocity.x, velocity.y),
+                from_player: from_player.is_some(),
+            })
+            .collect();
+
+        let bunker_cells = world
+            .query::<(&Transform, &BunkerCell)>()
+            .iter(world)
+            .map(|(transform, cell)| BunkerCellSave {
+                translation: transform.translation,
+                health: cell.health,
+            })
+            .collect();
+
+        let player = PlayerSave {
+            state: world.resource::<PlayerState>().clone(),
+            translation,
+        };
+
+        Self {
+            version: SAVE_VERSION,
+            score: world.resource::<Score>().0,
+            wave: world.resource::<Wave>().0,
+            wave_progress: world.resource::<WaveProgress>().0,
+            tick: world.resource::<GameClock>().tick,
+            rng: world.resource::<GameRng>().clone(),
+            tuning: world.resource::<Tuning>().clone(),
+            player,
+            enemies: EnemiesSave::from_world(world),
+            lasers,
+            bunker_cells,
+        }
+    }
+
+    /// Replaces the run on the field with the saved one
+    pub fn restore(self, world: &mut World) {
+        let field: Vec<Entity> = world
+            .query_filtered::<Entity, FieldEntity>()
+            .iter(world)
+            .collect();
+        for entity in field {
+            world.despawn(entity);
+        }
+
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, world);
+        let textures = world.resource::<GameTextures>();
+
+        if let Some(translation) = self.player.translation {
+            spawn_player(&mut commands, textures, translation);
+        }
+        let enemy_count = self.enemies.restore(&mut commands, textures);
+        for laser in self.lasers {
+            let entity = if laser.from_player {
+                spawn_player_laser(&mut commands, textures, laser.translation)
+            } else {
+                spawn_enemy_laser(&mut commands, textures, laser.translation)
+            };
+            commands.entity(entity).insert(Velocity {
+                x: laser.velocity.x,
+                y: laser.velocity.y,
+            });
+        }
+        for cell in self.bunker_cells {
+            spawn_bunker_cell(&mut commands, cell.translation, cell.health);
+        }
+        queue.apply(world);
+
+        world.insert_resource(Score(self.score));
+        world.insert_resource(Wave(self.wave));
+        world.insert_resource(WaveProgress(self.wave_progress));
+        world.insert_resource(EnemyCount(enemy_count));
+        world.insert_resource(BunkersBuilt(self.wave));
+        world.insert_resource(GameClock { tick: self.tick });
+        world.insert_resource(self.rng);
+        world.insert_resource(self.tuning);
+        world.insert_resource(self.player.state);
+        world.insert_resource(PlayerIntent::default());
+        // contacts of the replaced run must not reach the restored one
+        world.resource_mut::<Events<CollisionEvent>>().clear();
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|err| format!("could not read {}: {}", path.display(), err))?;
+
+        let header: SaveHeader = ron::from_str(&text)
+            .map_err(|err| format!("{} is not a saved game: {}", path.display(), err))?;
+        if header.version != SAVE_VERSION {
+            return Err(format!(
+                "{} is a version {} save, this game reads version {}",
+                path.display(),
+                header.version,
+                SAVE_VERSION
+            ));
+        }
+
+        ron::from_str(&text).map_err(|err| format!("{} is corrupted: {}", path.display(), err))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        let path = path.as_ref();
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|err| err.to_string())
+            .and_then(|text| fs::write(path, text).map_err(|err| err.to_string()))
+            .map_err(|err| format!("could not save {}: {}", path.display(), err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::difficulty::Difficulty;
+    use crate::replay::{Recording, ReplayPlugin};
+    use crate::settings::Settings;
+    use crate::snapshot::WorldSnapshot;
+    use crate::{headless_app, start_run, WinSize};
+
+    /// A run `ticks` in, played from inputs that only depend on the tick, so that a run
+    /// restored from it gets the same ones
+    fn played(seed: u64, ticks: usize) -> App {
+        let script = |tick: u64| PlayerIntent {
+            left: tick % 120 < 40,
+            right: tick % 120 >= 80,
+            fire: tick.is_multiple_of(15),
+        };
+        let win_size = WinSize {
+            width: 598.,
+            height: 676.,
+        };
+        let inputs = Recording::new(
+            seed,
+            Difficulty::Normal,
+            false,
+            1,
+            &win_size,
+            (1..=1000).map(script),
+        );
+
+        let mut app = headless_app(Settings::default(), seed, 1);
+        app.add_plugin(ReplayPlugin {
+            replay: Some(inputs),
+            record: None,
+        });
+        start_run(&mut app);
+        for _ in 0..ticks {
+            app.update();
+        }
+        app
+    }
+
+    #[test]
+    fn restored_runs_go_on_as_saved() {
+        let mut saved = played(3, 400);
+        let path = std::env::temp_dir().join("rust_invaders_round_trip.ron");
+        SaveGame::from_world(&mut saved.world).save(&path).unwrap();
+
+        // over another run in progress
+        let mut restored = played(99, 50);
+        SaveGame::load(&path).unwrap().restore(&mut restored.world);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            WorldSnapshot::from_world(&mut restored.world),
+            WorldSnapshot::from_world(&mut saved.world)
+        );
+        for _ in 0..300 {
+            saved.update();
+            restored.update();
+            let (expected, actual) = (
+                WorldSnapshot::from_world(&mut saved.world),
+                WorldSnapshot::from_world(&mut restored.world),
+            );
+            assert_eq!(
+                actual.checksum(),
+                expected.checksum(),
+                "diverged on tick {}",
+                expected.tick
+            );
+        }
+    }
+
+    #[test]
+    fn saves_of_other_versions_are_refused() {
+        let path = std::env::temp_dir().join("rust_invaders_old_save.ron");
+        fs::write(&path, "(version: 0, score: 10)").unwrap();
+        let err = SaveGame::load(&path).err().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            err,
+            format!(
+                "{} is a version 0 save, this game reads version {}",
+                path.display(),
+                SAVE_VERSION
+            )
+        );
+    }
+}
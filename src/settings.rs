@@ -0,0 +1,272 @@
+use crate::audio::AudioSettings;
+use crate::difficulty::{AdaptiveDifficulty, Difficulty};
+use bevy::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const HIGH_SCORES_MAX: usize = 10;
+
+/// Resource - keys of the player's actions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyBindings {
+    pub left: KeyCode,
+    pub right: KeyCode,
+    pub fire: KeyCode,
+    pub pause: KeyCode,
+    /// developer keys, only rebound in the settings file
+    pub debug: KeyCode,
+    pub console: KeyCode,
+    pub freeze: KeyCode,
+    pub step: KeyCode,
+    pub slower: KeyCode,
+    pub faster: KeyCode,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            left: KeyCode::Left,
+            right: KeyCode::Right,
+            fire: KeyCode::Space,
+            pause: KeyCode::Escape,
+            debug: KeyCode::F3,
+            console: KeyCode::Grave,
+            freeze: KeyCode::F5,
+            step: KeyCode::F6,
+            slower: KeyCode::F7,
+            faster: KeyCode::F8,
+        }
+    }
+}
+
+/// Resource - file the settings are saved to
+pub struct SettingsPath(pub PathBuf);
+
+impl Default for SettingsPath {
+    fn default() -> Self {
+        Self(PathBuf::from("settings.ron"))
+    }
+}
+
+/// Everything the settings screen edits, as saved on disk.
+/// At runtime each part lives in its own resource.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub audio: AudioSettings,
+    pub keys: KeyBindings,
+    pub difficulty: Difficulty,
+    pub adaptive: bool,
+}
+
+impl Settings {
+    /// Defaults if the file is missing or unreadable
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        load_ron(path.as_ref()).unwrap_or_default()
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) {
+        save_ron(path.as_ref(), self);
+    }
+
+    pub fn insert_into(self, app: &mut App) {
+        app.insert_resource(self.audio)
+            .insert_resource(self.keys)
+            .insert_resource(self.difficulty)
+            .insert_resource(AdaptiveDifficulty(self.adaptive));
+    }
+
+    /// The settings in effect, from their resources
+    pub fn from_world(world: &World) -> Self {
+        Self {
+            audio: world.resource::<AudioSettings>().clone(),
+            keys: world.resource::<KeyBindings>().clone(),
+            difficulty: *world.resource::<Difficulty>(),
+            adaptive: world.resource::<AdaptiveDifficulty>().0,
+        }
+    }
+
+    /// Sets the setting of `key`, as named in the settings file (e.g. `audio.volume`),
+    /// from its RON text
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        fn parse<T: DeserializeOwned>(key: &str, value: &str) -> Result<T, String> {
+            ron::from_str(value).map_err(|err| format!("bad value for {}: {}", key, err))
+        }
+
+        match key {
+            "audio.volume" => self.audio.volume = parse(key, value)?,
+            "audio.music_volume" => self.audio.music_volume = parse(key, value)?,
+            "audio.muted" => self.audio.muted = parse(key, value)?,
+            "keys.left" => self.keys.left = parse(key, value)?,
+            "keys.right" => self.keys.right = parse(key, value)?,
+            "keys.fire" => self.keys.fire = parse(key, value)?,
+            "keys.pause" => self.keys.pause = parse(key, value)?,
+            "keys.debug" => self.keys.debug = parse(key, value)?,
+            "keys.console" => self.keys.console = parse(key, value)?,
+            "keys.freeze" => self.keys.freeze = parse(key, value)?,
+            "keys.step" => self.keys.step = parse(key, value)?,
+            "keys.slower" => self.keys.slower = parse(key, value)?,
+            "keys.faster" => self.keys.faster = parse(key, value)?,
+            "difficulty" => self.difficulty = parse(key, value)?,
+            "adaptive" => self.adaptive = parse(key, value)?,
+            _ => return Err(format!("unknown setting {}", key)),
+        }
+        Ok(())
+    }
+
+    /// Puts the settings in effect, unlike `insert_into` after the app started
+    pub fn apply(self, world: &mut World) {
+        world.insert_resource(self.audio);
+        world.insert_resource(self.keys);
+        world.insert_resource(self.difficulty);
+        world.insert_resource(AdaptiveDifficulty(self.adaptive));
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HighScore {
+    pub score: u32,
+    pub wave: u32,
+    #[serde(default)]
+    pub difficulty: Difficulty,
+    #[serde(default)]
+    pub adaptive: bool,
+}
+
+/// Resource - best runs, highest score first
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HighScores {
+    pub entries: Vec<HighScore>,
+}
+
+impl HighScores {
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        load_ron(path.as_ref()).unwrap_or_default()
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) {
+        save_ron(path.as_ref(), self);
+    }
+
+    /// Keeps the run if it makes the table, returns whether it did
+    pub fn record(&mut self, entry: HighScore) -> bool {
+        if entry.score == 0 {
+            return false;
+        }
+
+        let index = self
+            .entries
+            .iter()
+            .position(|e| e.score < entry.score)
+            .unwrap_or(self.entries.len());
+        if index >= HIGH_SCORES_MAX {
+            return false;
+        }
+
+        self.entries.insert(index, entry);
+        self.entries.truncate(HIGH_SCORES_MAX);
+        true
+    }
+}
+
+pub(crate) fn load_ron<T: DeserializeOwned>(path: &Path) -> Option<T> {
+    let text = fs::read_to_string(path).ok()?;
+    match ron::from_str(&text) {
+        Ok(value) => Some(value),
+        Err(err) => {
+            warn!("ignoring {}: {}", path.display(), err);
+            None
+        }
+    }
+}
+
+fn save_ron<T: Serialize>(path: &Path, value: &T) {
+    let result = ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())
+        .map_err(|err| err.to_string())
+        .and_then(|text| fs::write(path, text).map_err(|err| err.to_string()));
+    if let Err(err) = result {
+        warn!("could not save {}: {}", path.display(), err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(score: u32) -> HighScore {
+        HighScore {
+            score,
+            wave: 1,
+            difficulty: Difficulty::Normal,
+            adaptive: false,
+        }
+    }
+
+    fn scores(high_scores: &HighScores) -> Vec<u32> {
+        high_scores
+            .entries
+            .iter()
+            .map(|entry| entry.score)
+            .collect()
+    }
+
+    #[test]
+    fn high_scores_keep_the_best_ten_in_order() {
+        let mut high_scores = HighScores::default();
+        assert!(!high_scores.record(run(0)));
+
+        for score in [50, 10, 30, 20, 40, 60, 90, 80, 70, 100] {
+            assert!(high_scores.record(run(score)));
+        }
+        assert_eq!(
+            scores(&high_scores),
+            vec![100, 90, 80, 70, 60, 50, 40, 30, 20, 10]
+        );
+
+        // too low for a full table, or tied with its last entry
+        assert!(!high_scores.record(run(5)));
+        assert!(!high_scores.record(run(10)));
+        assert_eq!(high_scores.entries.len(), HIGH_SCORES_MAX);
+
+        // ties go after the runs already there, the last one drops out
+        let mut tie = run(70);
+        tie.wave = 7;
+        assert!(high_scores.record(tie));
+        assert_eq!(
+            scores(&high_scores),
+            vec![100, 90, 80, 70, 70, 60, 50, 40, 30, 20]
+        );
+        assert_eq!(high_scores.entries[4].wave, 7);
+    }
+
+    #[test]
+    fn partial_settings_files_keep_the_defaults() {
+        let settings: Settings = ron::from_str("(audio: (volume: 0.3), difficulty: Hard)").unwrap();
+        assert_eq!(settings.audio.volume, 0.3);
+        assert_eq!(
+            settings.audio.music_volume,
+            AudioSettings::default().music_volume
+        );
+        assert_eq!(settings.difficulty, Difficulty::Hard);
+        assert!(!settings.adaptive);
+        assert_eq!(settings.keys.fire, KeyCode::Space);
+
+        // saved before the developer keys existed
+        let settings: Settings =
+            ron::from_str("(keys: (left: A, right: D, fire: W, pause: P), adaptive: true)")
+                .unwrap();
+        assert_eq!(settings.keys.left, KeyCode::A);
+        assert_eq!(settings.keys.pause, KeyCode::P);
+        assert_eq!(settings.keys.console, KeyCode::Grave);
+        assert_eq!(settings.keys.freeze, KeyCode::F5);
+        assert!(settings.adaptive);
+
+        // and old high scores without the run's difficulty
+        let high_scores: HighScores = ron::from_str("(entries: [(score: 120, wave: 3)])").unwrap();
+        assert_eq!(high_scores.entries[0].difficulty, Difficulty::default());
+    }
+}
@@ -0,0 +1,330 @@
+use crate::components::{Enemy, FromPlayer, Laser, Player, SpriteSize, Velocity};
+use crate::enemy::Formation;
+use crate::{EnemyCount, GameClock, PlayerState};
+use bevy::app::AppExit;
+use bevy::ecs::event::Events;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Plugin - keeps a checksum of the gameplay state for every tick of the current run,
+/// and writes them and / or the last snapshot when the app exits
+#[derive(Default)]
+pub struct SnapshotPlugin {
+    pub snapshot: Option<PathBuf>,
+    pub checksums: Option<PathBuf>,
+}
+
+impl Plugin for SnapshotPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Checksums::default())
+            .insert_resource(SnapshotFiles {
+                snapshot: self.snapshot.clone(),
+                checksums: self.checksums.clone(),
+            })
+            .add_system_to_stage(CoreStage::Last, checksum_system.exclusive_system())
+            .add_system_to_stage(
+                CoreStage::Last,
+                snapshot_exit_system.exclusive_system().at_end(),
+            );
+    }
+}
+
+/// Resource - `(tick, checksum)` of every tick of the current run, in order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Checksums(pub Vec<(u64, u64)>);
+
+struct SnapshotFiles {
+    snapshot: Option<PathBuf>,
+    checksums: Option<PathBuf>,
+}
+
+/// Gameplay state of the world at a tick. Entities are sorted by what they are and where,
+/// so snapshots of two runs compare equal whatever the order the entities were spawned in.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorldSnapshot {
+    pub tick: u64,
+    pub enemy_count: u32,
+    pub player: PlayerSnapshot,
+    pub entities: Vec<EntitySnapshot>,
+}
+
+/// `PlayerState`, which is not public
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PlayerSnapshot {
+    pub on: bool,
+    pub last_shot: f64,
+    pub lives: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EntitySnapshot {
+    pub kind: EntityKind,
+    pub translation: Vec3,
+    pub velocity: Option<Vec2>,
+    pub size: Option<Vec2>,
+    pub formation: Option<FormationSnapshot>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum EntityKind {
+    Player,
+    Enemy,
+    PlayerLaser,
+    EnemyLaser,
+}
+
+/// Where an enemy is in its formation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FormationSnapshot {
+    pub id: u32,
+    pub slot: u32,
+    pub angle: f32,
+    pub delay: f32,
+    pub segment: usize,
+    pub t: f32,
+}
+
+impl WorldSnapshot {
+    pub fn from_world(world: &mut World) -> Self {
+        let mut query = world.query_filtered::<(
+            &Transform,
+            Option<&Player>,
+            Option<&Enemy>,
+            Option<&FromPlayer>,
+            Option<&Velocity>,
+            Option<&SpriteSize>,
+            Option<&Formation>,
+        ), Or<(With<Player>, With<Enemy>, With<Laser>)>>();
+
+        let mut entities: Vec<EntitySnapshot> = query
+            .iter(world)
+            .map(
+                |(transform, player, enemy, from_player, velocity, size, formation)| {
+                    let kind = match (player, enemy, from_player) {
+                        (Some(_), _, _) => EntityKind::Player,
+                        (_, Some(_), _) => EntityKind::Enemy,
+                        (_, _, Some(_)) => EntityKind::PlayerLaser,
+                        _ => EntityKind::EnemyLaser,
+                    };
+                    EntitySnapshot {
+                        kind,
+                        translation: transform.translation,
+                        velocity: velocity.map(|velocity| Vec2::new(velocity.x, velocity.y)),
+                        size: size.map(|size| size.0),
+                        formation: formation.map(|formation| FormationSnapshot {
+                            id: formation.data.id.0,
+                            slot: formation.slot,
+                            angle: formation.angle,
+                            delay: formation.delay,
+                            segment: formation.cursor.segment,
+                            t: formation.cursor.t,
+                        }),
+                    }
+                },
+            )
+            .collect();
+        entities.sort_by(EntitySnapshot::order);
+
+        let player = world
+            .get_resource::<PlayerState>()
+            .map(|state| PlayerSnapshot {
+                on: state.on,
+                last_shot: state.last_shot,
+                lives: state.lives,
+            })
+            .unwrap_or_default();
+
+        Self {
+            tick: world
+                .get_resource::<GameClock>()
+                .map_or(0, |clock| clock.tick),
+            enemy_count: world
+                .get_resource::<EnemyCount>()
+                .map_or(0, |count| count.0),
+            player,
+            entities,
+        }
+    }
+
+    /// FNV-1a of the RON text, the same on every platform and run
+    pub fn checksum(&self) -> u64 {
+        let text = ron::to_string(self).unwrap_or_default();
+        text.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        })
+    }
+
+    pub fn to_ron(&self) -> String {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, self.to_ron())
+            .map_err(|err| format!("could not write {}: {}", path.display(), err))
+    }
+}
+
+impl EntitySnapshot {
+    fn order(&self, other: &Self) -> Ordering {
+        let position = |entity: &Self| entity.translation.to_array();
+        self.kind.cmp(&other.kind).then_with(|| {
+            position(self)
+                .iter()
+                .zip(position(other).iter())
+                .map(|(a, b)| a.total_cmp(b))
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or(Ordering::Equal)
+        })
+    }
+}
+
+fn checksum_system(world: &mut World) {
+    let tick = match world.get_resource::<GameClock>() {
+        Some(clock) => clock.tick,
+        None => return,
+    };
+    let last = world
+        .resource::<Checksums>()
+        .0
+        .last()
+        .map(|(tick, _)| *tick);
+    if last == Some(tick) || tick == 0 {
+        return;
+    }
+
+    let checksum = WorldSnapshot::from_world(world).checksum();
+    let mut checksums = world.resource_mut::<Checksums>();
+    // a new run started, only the last one is kept
+    if last.is_some_and(|last| tick < last) {
+        checksums.0.clear();
+    }
+    checksums.0.push((tick, checksum));
+}
+
+fn snapshot_exit_system(world: &mut World) {
+    if world.resource::<Events<AppExit>>().is_empty() {
+        return;
+    }
+
+    let files = world.resource::<SnapshotFiles>();
+    let (snapshot_path, checksums_path) = (files.snapshot.clone(), files.checksums.clone());
+
+    if let Some(path) = snapshot_path {
+        if let Err(err) = WorldSnapshot::from_world(world).save(&path) {
+            error!("{}", err);
+        }
+    }
+
+    if let Some(path) = checksums_path {
+        let mut text = String::new();
+        for (tick, checksum) in world.resource::<Checksums>().0.iter() {
+            let _ = writeln!(text, "{} {:016x}", tick, checksum);
+        }
+        if let Err(err) = fs::write(&path, text) {
+            error!("could not write {}: {}", path.display(), err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::Settings;
+    use crate::{headless_app, start_run};
+
+    fn headless_run(seed: u64, ticks: usize) -> App {
+        let mut app = headless_app(Settings::default(), seed, 1);
+        app.add_plugin(SnapshotPlugin::default());
+        start_run(&mut app);
+        for _ in 0..ticks {
+            app.update();
+        }
+        app
+    }
+
+    fn spawn(world: &mut World, kind: EntityKind, x: f32) {
+        let mut entity = world.spawn();
+        entity.insert(Transform::from_xyz(x, 0., 0.));
+        match kind {
+            EntityKind::Player => entity.insert(Player),
+            EntityKind::Enemy => entity.insert(Enemy),
+            EntityKind::PlayerLaser => entity.insert(Laser).insert(FromPlayer),
+            EntityKind::EnemyLaser => entity.insert(Laser),
+        };
+    }
+
+    #[test]
+    fn same_seed_runs_have_the_same_checksums() {
+        let mut first = headless_run(11, 300);
+        let mut second = headless_run(11, 300);
+
+        let checksums = first.world.resource::<Checksums>().clone();
+        assert_eq!(checksums.0.len(), 300);
+        assert_eq!(&checksums, second.world.resource::<Checksums>());
+        assert_eq!(
+            WorldSnapshot::from_world(&mut first.world),
+            WorldSnapshot::from_world(&mut second.world)
+        );
+    }
+
+    #[test]
+    fn snapshots_do_not_depend_on_spawn_order() {
+        let mut first = World::new();
+        spawn(&mut first, EntityKind::Enemy, 10.);
+        spawn(&mut first, EntityKind::EnemyLaser, 0.);
+        spawn(&mut first, EntityKind::Enemy, -10.);
+        spawn(&mut first, EntityKind::Player, 0.);
+
+        let mut second = World::new();
+        spawn(&mut second, EntityKind::Player, 0.);
+        spawn(&mut second, EntityKind::Enemy, -10.);
+        spawn(&mut second, EntityKind::EnemyLaser, 0.);
+        spawn(&mut second, EntityKind::Enemy, 10.);
+
+        let snapshot = WorldSnapshot::from_world(&mut first);
+        assert_eq!(snapshot, WorldSnapshot::from_world(&mut second));
+        assert_eq!(
+            snapshot
+                .entities
+                .iter()
+                .map(|entity| entity.kind)
+                .collect::<Vec<_>>(),
+            vec![
+                EntityKind::Player,
+                EntityKind::Enemy,
+                EntityKind::Enemy,
+                EntityKind::EnemyLaser
+            ]
+        );
+    }
+
+    #[test]
+    fn checksum_follows_the_state() {
+        let mut world = World::new();
+        spawn(&mut world, EntityKind::Enemy, 10.);
+        spawn(&mut world, EntityKind::PlayerLaser, 0.);
+        let before = WorldSnapshot::from_world(&mut world);
+
+        for mut transform in world.query::<&mut Transform>().iter_mut(&mut world) {
+            transform.translation.y += 0.001;
+        }
+        let after = WorldSnapshot::from_world(&mut world);
+
+        assert_eq!(before.checksum(), before.clone().checksum());
+        assert_ne!(before.checksum(), after.checksum());
+    }
+
+    #[test]
+    fn ron_round_trip() {
+        let mut app = headless_run(3, 120);
+        let snapshot = WorldSnapshot::from_world(&mut app.world);
+
+        let parsed: WorldSnapshot = ron::from_str(&snapshot.to_ron()).unwrap();
+        assert_eq!(parsed, snapshot);
+        assert_eq!(parsed.checksum(), snapshot.checksum());
+    }
+}
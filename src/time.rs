@@ -0,0 +1,213 @@
+use crate::TIME_STEP;
+use bevy::prelude::*;
+use bevy::window::PresentMode;
+
+/// Resource - how fast game time runs. Game time is played in ticks of `TIME_STEP`, at most
+/// one per frame, as the frame time scaled by `scale` adds up: windowed runs count the real
+/// time between frames, whatever the refresh rate, headless ones `TIME_STEP` per update.
+/// Frozen, game time stands still but for the ticks asked for with `step`.
+///
+/// A frame runs the schedule once, so it never plays more than one tick: above 1x, game time
+/// only runs faster when frames come faster than ticks (uncapped windowed frames). Headless
+/// runs play a tick per update at any scale of 1 or more, fast-forwarding them means updating
+/// more often.
+pub struct TimeScale {
+    pub scale: f32,
+    pub frozen: bool,
+    pub real_time: bool, // paced by the real time between frames, or a tick per update at 1
+    steps: u32,          // ticks to play while frozen
+    owed: f32,           // ticks of game time not played yet
+    pub(crate) due: bool, // whether this frame plays a tick
+}
+
+impl Default for TimeScale {
+    fn default() -> Self {
+        Self {
+            scale: 1.,
+            frozen: false,
+            real_time: false,
+            steps: 0,
+            owed: 0.,
+            due: true,
+        }
+    }
+}
+
+impl TimeScale {
+    pub const MIN: f32 = 0.1;
+    pub const MAX: f32 = 4.;
+    /// Scales the slower and faster keys go through
+    pub const STEPS: [f32; 6] = [0.1, 0.25, 0.5, 1., 2., 4.];
+
+    /// Game time of windowed runs
+    pub fn real_time() -> Self {
+        Self {
+            real_time: true,
+            ..default()
+        }
+    }
+
+    /// Freezes game time, then plays `ticks` ticks, one per frame
+    pub fn step(&mut self, ticks: u32) {
+        self.frozen = true;
+        self.steps += ticks;
+    }
+
+    pub fn slower(&mut self) {
+        self.scale = Self::STEPS
+            .iter()
+            .rev()
+            .copied()
+            .find(|scale| *scale < self.scale)
+            .unwrap_or(Self::MIN);
+    }
+
+    pub fn faster(&mut self) {
+        self.scale = Self::STEPS
+            .iter()
+            .copied()
+            .find(|scale| *scale > self.scale)
+            .unwrap_or(Self::MAX);
+    }
+
+    /// Plays the game time of a frame of `frame_seconds`
+    pub(crate) fn advance(&mut self, frame_seconds: f32) {
+        if self.frozen {
+            self.owed = 0.;
+            self.due = self.steps > 0;
+            self.steps = self.steps.saturating_sub(1);
+            return;
+        }
+        self.steps = 0;
+
+        // no catching up in bursts after a slow frame
+        self.owed = (self.owed + frame_seconds * self.scale / TIME_STEP).min(2.);
+        self.due = self.owed >= 1.;
+        if self.due {
+            self.owed -= 1.;
+        }
+    }
+}
+
+/// Decides whether this frame plays a tick. Frames are uncapped while the game runs fast,
+/// a tick per vsynced frame would be its top speed.
+pub(crate) fn time_scale_system(
+    time: Res<Time>,
+    mut time_scale: ResMut<TimeScale>,
+    windows: Option<ResMut<Windows>>,
+) {
+    let frame_seconds = if time_scale.real_time {
+        time.delta_seconds()
+    } else {
+        TIME_STEP
+    };
+    time_scale.advance(frame_seconds);
+
+    let present_mode = if time_scale.scale > 1. {
+        PresentMode::Immediate
+    } else {
+        PresentMode::Fifo
+    };
+    let mut windows = match windows {
+        Some(windows) => windows,
+        None => return,
+    };
+    if let Some(window) = windows.get_primary_mut() {
+        if window.present_mode() != present_mode {
+            window.set_present_mode(present_mode);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ticks played over `seconds` of frames `frame_seconds` apart
+    fn ticks(time_scale: &mut TimeScale, frame_seconds: f32, seconds: f32) -> u32 {
+        let frames = (seconds / frame_seconds).round() as u32;
+        (0..frames)
+            .filter(|_| {
+                time_scale.advance(frame_seconds);
+                time_scale.due
+            })
+            .count() as u32
+    }
+
+    #[test]
+    fn real_time_plays_the_same_ticks_at_any_refresh_rate() {
+        for hz in [60., 75., 120., 144., 240.] {
+            let played = ticks(&mut TimeScale::real_time(), 1. / hz, 10.);
+            assert!(
+                (599..=601).contains(&played),
+                "{} ticks at {} Hz",
+                played,
+                hz
+            );
+        }
+        // at most one a frame: slow frames slow the game down rather than jump ahead
+        assert_eq!(ticks(&mut TimeScale::real_time(), 1. / 30., 10.), 300);
+    }
+
+    #[test]
+    fn headless_runs_play_a_tick_per_update() {
+        let mut time_scale = TimeScale::default();
+        assert_eq!(ticks(&mut time_scale, TIME_STEP, 1.), 60);
+        time_scale.scale = 0.5;
+        assert_eq!(ticks(&mut time_scale, TIME_STEP, 1.), 30);
+    }
+
+    #[test]
+    fn scaled_frame_time_adds_up_to_ticks() {
+        // at 1x, half-tick frames play a tick every other frame
+        let mut time_scale = TimeScale::real_time();
+        let due: Vec<bool> = (0..4)
+            .map(|_| {
+                time_scale.advance(TIME_STEP / 2.);
+                time_scale.due
+            })
+            .collect();
+        assert_eq!(due, [false, true, false, true]);
+
+        // slowed down, a tick every fourth tick-long frame
+        time_scale.scale = 0.25;
+        assert_eq!(ticks(&mut time_scale, TIME_STEP, 1.), 15);
+    }
+
+    #[test]
+    fn fast_forward_is_capped_at_a_tick_per_frame() {
+        // uncapped frames: twice the ticks of 1x over the same frames
+        let mut time_scale = TimeScale::real_time();
+        assert_eq!(ticks(&mut time_scale, 1. / 240., 1.), 60);
+        time_scale.scale = 2.;
+        assert_eq!(ticks(&mut time_scale, 1. / 240., 1.), 120);
+
+        // frames as long as a tick already play one each
+        assert_eq!(ticks(&mut time_scale, TIME_STEP, 1.), 60);
+        let mut headless = TimeScale {
+            scale: 2.,
+            ..default()
+        };
+        assert_eq!(ticks(&mut headless, TIME_STEP, 1.), 60);
+    }
+
+    #[test]
+    fn frozen_time_plays_only_the_steps() {
+        let mut time_scale = TimeScale {
+            frozen: true,
+            ..default()
+        };
+        assert_eq!(ticks(&mut time_scale, TIME_STEP, 1.), 0);
+
+        time_scale.step(3);
+        assert_eq!(ticks(&mut time_scale, TIME_STEP, 1.), 3);
+        assert!(time_scale.frozen);
+
+        // steps left over when unfrozen are dropped
+        time_scale.step(5);
+        time_scale.frozen = false;
+        assert_eq!(ticks(&mut time_scale, TIME_STEP, 1.), 60);
+        time_scale.frozen = true;
+        assert_eq!(ticks(&mut time_scale, TIME_STEP, 1.), 0);
+    }
+}
@@ -0,0 +1,170 @@
+//! Golden runs: headless runs with a fixed seed and scripted inputs, whose final snapshot
+//! must match the one checked in under `tests/golden/`.
+//!
+//! After an intended change of behaviour, regenerate the files and review their diff:
+//!
+//!     UPDATE_GOLDENS=1 cargo test --test golden
+
+use bevy::prelude::*;
+use rust_invaders::difficulty::Difficulty;
+use rust_invaders::replay::{Recording, ReplayPlugin};
+use rust_invaders::settings::Settings;
+use rust_invaders::snapshot::WorldSnapshot;
+use rust_invaders::{headless_app, start_run, PlayerIntent, WinSize};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+struct Scenario {
+    name: &'static str,
+    seed: u64,
+    difficulty: Difficulty,
+    start_wave: u32,
+    ticks: u64,
+    /// intent of each tick, ticks start at 1
+    script: fn(u64) -> PlayerIntent,
+}
+
+impl Scenario {
+    fn run(&self) -> WorldSnapshot {
+        let settings = Settings {
+            difficulty: self.difficulty,
+            ..default()
+        };
+        let mut app = headless_app(settings, self.seed, self.start_wave);
+        app.add_plugin(ReplayPlugin {
+            replay: Some(self.recording()),
+            record: None,
+        });
+        start_run(&mut app);
+
+        for _ in 0..self.ticks {
+            app.update();
+        }
+        WorldSnapshot::from_world(&mut app.world)
+    }
+
+    /// The script, played back as a recording of the run
+    fn recording(&self) -> Recording {
+        Recording::new(
+            self.seed,
+            self.difficulty,
+            false,
+            self.start_wave,
+            &WinSize {
+                width: 598.,
+                height: 676.,
+            },
+            (1..=self.ticks).map(self.script),
+        )
+    }
+
+    fn golden_path(&self) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/golden")
+            .join(format!("{}.ron", self.name))
+    }
+
+    fn check(&self) {
+        let actual = self.run().to_ron();
+        let path = self.golden_path();
+
+        if env::var_os("UPDATE_GOLDENS").is_some() {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, &actual).unwrap();
+            return;
+        }
+
+        let expected = fs::read_to_string(&path).unwrap_or_else(|err| {
+            panic!(
+                "could not read {} ({}), create it with UPDATE_GOLDENS=1",
+                path.display(),
+                err
+            )
+        });
+        if let Some((line, (expected, actual))) = expected
+            .lines()
+            .zip(actual.lines())
+            .enumerate()
+            .find(|(_, (expected, actual))| expected != actual)
+        {
+            panic!(
+                "{} differs from {} at line {}:\n  expected: {}\n  actual:   {}\n\
+                 if the change is intended, run with UPDATE_GOLDENS=1 and review the diff",
+                self.name,
+                path.display(),
+                line + 1,
+                expected.trim(),
+                actual.trim()
+            );
+        }
+        assert_eq!(
+            expected.lines().count(),
+            actual.lines().count(),
+            "{} is longer or shorter than {}, run with UPDATE_GOLDENS=1 if intended",
+            self.name,
+            path.display()
+        );
+    }
+}
+
+fn idle(_: u64) -> PlayerIntent {
+    PlayerIntent::default()
+}
+
+/// Sweeps left and right, two seconds each way, firing every 10 ticks
+fn sweep_and_fire(tick: u64) -> PlayerIntent {
+    let left = (tick / 120).is_multiple_of(2);
+    PlayerIntent {
+        left,
+        right: !left,
+        fire: tick.is_multiple_of(10),
+    }
+}
+
+/// Stands still under the enemies' paths and keeps firing
+fn turret(tick: u64) -> PlayerIntent {
+    PlayerIntent {
+        fire: tick.is_multiple_of(8),
+        ..default()
+    }
+}
+
+#[test]
+fn idle_run() {
+    Scenario {
+        name: "idle",
+        seed: 1,
+        difficulty: Difficulty::Normal,
+        start_wave: 1,
+        ticks: 600,
+        script: idle,
+    }
+    .check();
+}
+
+#[test]
+fn sweep_and_fire_run() {
+    Scenario {
+        name: "sweep_and_fire",
+        seed: 2,
+        difficulty: Difficulty::Normal,
+        start_wave: 1,
+        ticks: 1200,
+        script: sweep_and_fire,
+    }
+    .check();
+}
+
+#[test]
+fn hard_turret_run() {
+    Scenario {
+        name: "hard_turret",
+        seed: 3,
+        difficulty: Difficulty::Hard,
+        start_wave: 3,
+        ticks: 900,
+        script: turret,
+    }
+    .check();
+}